@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+
+use crate::Chunk;
+
+/// World positions of every voxel the box from `min` to `max` overlaps. Voxels the box only
+/// touches along a face are left out, so a box exactly filling one voxel yields just that voxel.
+pub fn voxels_in_aabb(min: Vec3, max: Vec3) -> impl Iterator<Item = IVec3> {
+    let start = min.floor().as_ivec3();
+    let end = max.ceil().as_ivec3();
+
+    (start.x..end.x).flat_map(move |x| {
+        (start.y..end.y).flat_map(move |y| (start.z..end.z).map(move |z| IVec3::new(x, y, z)))
+    })
+}
+
+/// Whether the box from `min` to `max`, in world space, overlaps a solid block in any of `chunks`.
+pub fn aabb_overlaps_solid<'a>(
+    min: Vec3,
+    max: Vec3,
+    chunks: impl IntoIterator<Item = &'a Chunk>,
+) -> bool {
+    chunks.into_iter().any(|chunk| {
+        let origin = chunk.origin();
+        voxels_in_aabb(min, max).any(
+            |pos| matches!(chunk.get_block(pos - origin), Some(block) if !block.is_transparent()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockType, CHUNK_SIZE};
+
+    fn solid_chunk(chunk_coords: IVec3) -> Chunk {
+        let mut chunk = Chunk {
+            chunk_coords,
+            ..default()
+        };
+        for (_, block) in chunk.iter_blocks_mut() {
+            *block = BlockType::Stone;
+        }
+        chunk
+    }
+
+    #[test]
+    fn box_inside_one_voxel_overlaps_only_it() {
+        let voxels: Vec<_> =
+            voxels_in_aabb(Vec3::new(2.2, 3.1, -0.9), Vec3::new(2.8, 3.9, -0.1)).collect();
+        assert_eq!(voxels, vec![IVec3::new(2, 3, -1)]);
+    }
+
+    #[test]
+    fn box_spanning_several_voxels_overlaps_each_partial_voxel() {
+        let mut voxels: Vec<_> =
+            voxels_in_aabb(Vec3::new(0.5, 0.5, 0.5), Vec3::new(2.5, 1.5, 0.9)).collect();
+        voxels.sort_by_key(|pos| (pos.x, pos.y, pos.z));
+        assert_eq!(
+            voxels,
+            vec![
+                IVec3::new(0, 0, 0),
+                IVec3::new(0, 1, 0),
+                IVec3::new(1, 0, 0),
+                IVec3::new(1, 1, 0),
+                IVec3::new(2, 0, 0),
+                IVec3::new(2, 1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn box_touching_a_face_does_not_overlap_the_neighbour() {
+        let voxels: Vec<_> = voxels_in_aabb(Vec3::ZERO, Vec3::ONE).collect();
+        assert_eq!(voxels, vec![IVec3::ZERO]);
+
+        // Resting exactly on top of a solid chunk
+        let chunk = solid_chunk(IVec3::ZERO);
+        let top = CHUNK_SIZE.y as f32;
+        assert!(!aabb_overlaps_solid(
+            Vec3::new(1., top, 1.),
+            Vec3::new(2., top + 2., 2.),
+            [&chunk]
+        ));
+        assert!(aabb_overlaps_solid(
+            Vec3::new(1., top - 0.01, 1.),
+            Vec3::new(2., top + 2., 2.),
+            [&chunk]
+        ));
+    }
+
+    #[test]
+    fn box_spanning_a_chunk_border_checks_both_chunks() {
+        let border = CHUNK_SIZE.x as f32;
+        let min = Vec3::new(border - 0.5, 1., 1.);
+        let max = Vec3::new(border + 0.5, 2., 2.);
+
+        let near = solid_chunk(IVec3::ZERO);
+        let far = solid_chunk(IVec3::X);
+        let empty_near = Chunk::default();
+        let empty_far = Chunk {
+            chunk_coords: IVec3::X,
+            ..default()
+        };
+
+        assert!(aabb_overlaps_solid(min, max, [&empty_near, &far]));
+        assert!(aabb_overlaps_solid(min, max, [&near, &empty_far]));
+        assert!(!aabb_overlaps_solid(min, max, [&empty_near, &empty_far]));
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::render_resource::Face,
+};
+
+use crate::{raycast::raycast_chunks, Chunk, MainCamera};
+
+/// Outlines the block under the crosshair.
+pub struct BlockOutlinePlugin;
+
+impl Plugin for BlockOutlinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlockOutlineSettings>()
+            .add_startup_system(setup_block_outline)
+            .add_system(block_outline_system)
+            .add_system(apply_block_outline_settings);
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct BlockOutlineSettings {
+    pub enabled: bool,
+    /// How far away, in blocks, a targeted block is still outlined.
+    pub max_distance: f32,
+    pub color: Color,
+    /// Width of the outline, in blocks.
+    pub thickness: f32,
+}
+
+impl Default for BlockOutlineSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_distance: 16.,
+            color: Color::BLACK,
+            thickness: 0.02,
+        }
+    }
+}
+
+#[derive(Component)]
+struct BlockOutline;
+
+fn setup_block_outline(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<BlockOutlineSettings>,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1. })),
+            material: materials.add(StandardMaterial {
+                base_color: settings.color,
+                unlit: true,
+                // Inverted hull: only the back faces of a slightly larger cube are drawn, and the
+                // targeted block hides all of them except a rim around its silhouette
+                cull_mode: Some(Face::Front),
+                ..default()
+            }),
+            transform: Transform::from_scale(Vec3::splat(1. + 2. * settings.thickness)),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        },
+        NotShadowCaster,
+        NotShadowReceiver,
+        BlockOutline,
+    ));
+}
+
+fn block_outline_system(
+    settings: Res<BlockOutlineSettings>,
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+    chunks: Query<&Chunk>,
+    mut outlines: Query<(&mut Transform, &mut Visibility), With<BlockOutline>>,
+) {
+    let Ok((mut transform, mut visibility)) = outlines.get_single_mut() else {
+        return;
+    };
+
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+
+    let camera = camera.compute_transform();
+    let target = if settings.enabled {
+        raycast_chunks(
+            chunks.iter(),
+            camera.translation,
+            camera.forward(),
+            settings.max_distance,
+        )
+    } else {
+        None
+    };
+
+    let Some((chunk, hit)) = target else {
+        visibility.is_visible = false;
+        return;
+    };
+
+    let world_pos = chunk.origin() + hit.pos;
+    transform.translation = world_pos.as_vec3() + Vec3::splat(0.5);
+    visibility.is_visible = true;
+}
+
+fn apply_block_outline_settings(
+    settings: Res<BlockOutlineSettings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut outlines: Query<(&mut Transform, &Handle<StandardMaterial>), With<BlockOutline>>,
+) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+
+    for (mut transform, handle) in outlines.iter_mut() {
+        transform.scale = Vec3::splat(1. + 2. * settings.thickness);
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = settings.color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockType;
+
+    #[test]
+    fn outline_follows_the_targeted_block() {
+        let mut app = App::new();
+        app.init_resource::<BlockOutlineSettings>()
+            .add_system(block_outline_system);
+        let outline = app
+            .world
+            .spawn((
+                Transform::default(),
+                Visibility { is_visible: false },
+                BlockOutline,
+            ))
+            .id();
+        let mut chunk = Chunk {
+            chunk_coords: IVec3::X,
+            ..default()
+        };
+        *chunk.get_block_mut(IVec3::new(4, 1, 1)).unwrap() = BlockType::Stone;
+        *chunk.get_block_mut(IVec3::new(4, 3, 1)).unwrap() = BlockType::Stone;
+        app.world.spawn(chunk);
+        let camera = app.world.spawn(MainCamera).id();
+
+        let aim_from = |app: &mut App, eye: Vec3| {
+            app.world.entity_mut(camera).insert(GlobalTransform::from(
+                Transform::from_translation(eye).looking_at(eye + Vec3::X, Vec3::Y),
+            ));
+            app.update();
+            let visible = app.world.get::<Visibility>(outline).unwrap().is_visible;
+            let center = app.world.get::<Transform>(outline).unwrap().translation;
+            visible.then_some(center)
+        };
+
+        assert_eq!(
+            aim_from(&mut app, Vec3::new(16.5, 1.5, 1.5)),
+            Some(Vec3::new(20.5, 1.5, 1.5))
+        );
+        assert_eq!(
+            aim_from(&mut app, Vec3::new(16.5, 3.5, 1.5)),
+            Some(Vec3::new(20.5, 3.5, 1.5))
+        );
+        assert_eq!(aim_from(&mut app, Vec3::new(16.5, 5.5, 1.5)), None);
+    }
+}
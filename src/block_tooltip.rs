@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+
+use crate::{raycast::raycast_chunks, voxel_search::has_sky_access, BlockType, Chunk, MainCamera};
+
+/// Shows the type and coordinate of the block under the crosshair, and whether it is open to the
+/// sky.
+pub struct BlockTooltipPlugin;
+
+impl Plugin for BlockTooltipPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlockTooltipSettings>()
+            .add_startup_system(setup_block_tooltip)
+            .add_system(block_tooltip_system);
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct BlockTooltipSettings {
+    pub enabled: bool,
+    /// How far away, in blocks, a targeted block is still described.
+    pub max_distance: f32,
+    pub font_size: f32,
+    /// Offset from the center of the screen, in logical pixels.
+    pub offset: Vec2,
+}
+
+impl Default for BlockTooltipSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_distance: 16.,
+            font_size: 16.,
+            offset: Vec2::new(12., 12.),
+        }
+    }
+}
+
+#[derive(Component)]
+struct BlockTooltip;
+
+fn setup_block_tooltip(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<BlockTooltipSettings>,
+) {
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/DejaVuSansMono.ttf"),
+                    font_size: settings.font_size,
+                    color: Color::WHITE,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(50.),
+                    top: Val::Percent(50.),
+                    ..default()
+                },
+                margin: UiRect {
+                    left: Val::Px(settings.offset.x),
+                    top: Val::Px(settings.offset.y),
+                    ..default()
+                },
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        },
+        BlockTooltip,
+    ));
+}
+
+/// What the tooltip currently describes, so its text is only rebuilt when that changes.
+#[derive(Clone, Copy, PartialEq)]
+struct TooltipTarget {
+    world_pos: IVec3,
+    block: BlockType,
+    sky_access: bool,
+}
+
+fn block_tooltip_system(
+    settings: Res<BlockTooltipSettings>,
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+    chunks: Query<&Chunk>,
+    mut tooltips: Query<(&mut Text, &mut Visibility), With<BlockTooltip>>,
+    mut shown: Local<Option<TooltipTarget>>,
+) {
+    let Ok((mut text, mut visibility)) = tooltips.get_single_mut() else {
+        return;
+    };
+
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+
+    let camera = camera.compute_transform();
+    let target = if settings.enabled {
+        raycast_chunks(
+            chunks.iter(),
+            camera.translation,
+            camera.forward(),
+            settings.max_distance,
+        )
+    } else {
+        None
+    };
+
+    let Some((chunk, hit)) = target else {
+        if visibility.is_visible {
+            visibility.is_visible = false;
+        }
+        *shown = None;
+        return;
+    };
+
+    let world_pos = chunk.origin() + hit.pos;
+    let target = TooltipTarget {
+        world_pos,
+        block: *chunk.get_block(hit.pos).unwrap(),
+        sky_access: has_sky_access(world_pos, chunks.iter()),
+    };
+    if *shown != Some(target) {
+        let sky = if target.sky_access {
+            ", open to the sky"
+        } else {
+            ""
+        };
+        text.sections[0].value = format!(
+            "{} ({}, {}, {}){}",
+            target.block, world_pos.x, world_pos.y, world_pos.z, sky
+        );
+        *shown = Some(target);
+    }
+
+    if !visibility.is_visible {
+        visibility.is_visible = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tooltip_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<BlockTooltipSettings>()
+            .add_system(block_tooltip_system);
+        app.world.spawn((
+            Text::from_section("", TextStyle::default()),
+            Visibility { is_visible: false },
+            BlockTooltip,
+        ));
+        app
+    }
+
+    fn tooltip(app: &mut App) -> (String, bool) {
+        let (text, visibility) = app
+            .world
+            .query_filtered::<(&Text, &Visibility), With<BlockTooltip>>()
+            .single(&app.world);
+        (text.sections[0].value.clone(), visibility.is_visible)
+    }
+
+    #[test]
+    fn tooltip_describes_the_targeted_block() {
+        let mut app = tooltip_app();
+        let mut chunk = Chunk::default();
+        *chunk.get_block_mut(IVec3::new(4, 1, 1)).unwrap() = BlockType::Stone;
+        *chunk.get_block_mut(IVec3::new(4, 3, 1)).unwrap() = BlockType::Grass;
+        app.world.spawn(chunk);
+        let eye = Vec3::new(0.5, 1.5, 1.5);
+        let camera = app
+            .world
+            .spawn((
+                MainCamera,
+                GlobalTransform::from(
+                    Transform::from_translation(eye).looking_at(eye + Vec3::X, Vec3::Y),
+                ),
+            ))
+            .id();
+
+        app.update();
+        assert_eq!(tooltip(&mut app), ("Stone (4, 1, 1)".to_string(), true));
+
+        // Still aimed at the same block, the text is left alone
+        app.world
+            .query::<&mut Text>()
+            .single_mut(&mut app.world)
+            .sections[0]
+            .value = "unchanged".to_string();
+        app.update();
+        assert_eq!(tooltip(&mut app).0, "unchanged");
+
+        let eye = Vec3::new(0.5, 3.5, 1.5);
+        *app.world.get_mut::<GlobalTransform>(camera).unwrap() = Transform::from_translation(eye)
+            .looking_at(eye + Vec3::X, Vec3::Y)
+            .into();
+        app.update();
+        assert_eq!(
+            tooltip(&mut app),
+            ("Grass (4, 3, 1), open to the sky".to_string(), true)
+        );
+
+        let eye = Vec3::new(0.5, 5.5, 1.5);
+        *app.world.get_mut::<GlobalTransform>(camera).unwrap() = Transform::from_translation(eye)
+            .looking_at(eye + Vec3::X, Vec3::Y)
+            .into();
+        app.update();
+        assert!(!tooltip(&mut app).1);
+    }
+}
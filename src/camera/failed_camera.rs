@@ -1,11 +1,18 @@
-use bevy::{input::mouse::MouseMotion, prelude::*};
+use bevy::{input::mouse::MouseMotion, prelude::*, time::FixedTimestep};
+
+use crate::{InputSettings, TIME_STEP};
 
 #[derive(Component)]
 pub struct FailedCamera;
 
+/// Roll, in radians, currently applied on top of the camera's pitch and yaw.
+#[derive(Component, Default)]
+pub struct CameraRoll(pub f32);
+
 #[derive(Bundle)]
 pub struct FailedCameraBundle {
     pub failed_camera: FailedCamera,
+    pub roll: CameraRoll,
     pub camera_bundle: Camera3dBundle,
 }
 
@@ -14,55 +21,108 @@ pub struct FailedCameraPlugin {}
 
 impl Plugin for FailedCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(camera_movement_system);
+        app.init_resource::<CameraRollSettings>()
+            .add_system(camera_look_system)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::step(TIME_STEP as f64))
+                    .with_system(camera_movement_system),
+            );
     }
 }
 
-pub fn camera_movement_system(
-    mut q: Query<&mut Transform, With<FailedCamera>>,
+/// Leans the camera into sideways movement.
+#[derive(Resource, Clone, Debug)]
+pub struct CameraRollSettings {
+    /// Roll, in radians, while strafing. Zero turns leaning off.
+    pub amplitude: f32,
+    /// How quickly the roll eases towards its target, per second.
+    pub speed: f32,
+}
+
+impl Default for CameraRollSettings {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.,
+            speed: 8.,
+        }
+    }
+}
+
+/// Runs every frame so looking around stays as responsive as the frame rate allows.
+pub fn camera_look_system(
+    mut q: Query<(&mut Transform, &mut CameraRoll), With<FailedCamera>>,
     keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    input_settings: Res<InputSettings>,
+    roll_settings: Res<CameraRollSettings>,
     mut mouse_evr: EventReader<MouseMotion>,
 ) {
-    let mut transform = q.get_single_mut().unwrap();
+    let (mut transform, mut roll) = q.get_single_mut().unwrap();
+
+    // Pitch and yaw are applied without the roll, so leaning doesn't skew where mouse motion
+    // turns the camera
+    transform.rotate_local_z(-roll.0);
+
+    let mouse_motion = mouse_evr.iter().fold(Vec2::ZERO, |acc, ev| acc + ev.delta);
+    let look_delta = input_settings.look_delta(mouse_motion);
+
+    transform.rotate_local_x(-look_delta.y);
+    transform.rotate_local_y(-look_delta.x);
+
+    let target_roll = match (keys.pressed(KeyCode::A), keys.pressed(KeyCode::D)) {
+        (true, false) => roll_settings.amplitude,
+        (false, true) => -roll_settings.amplitude,
+        _ => 0.,
+    };
+    let ease = 1. - (-roll_settings.speed * time.delta_seconds()).exp();
+    roll.0 += (target_roll - roll.0) * ease;
+
+    transform.rotate_local_z(roll.0);
+}
+
+/// Runs at a fixed timestep so the distance moved per second doesn't depend on the frame rate.
+pub fn camera_movement_system(
+    mut q: Query<(&mut Transform, &CameraRoll), With<FailedCamera>>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let (mut transform, roll) = q.get_single_mut().unwrap();
+
+    // Move relative to the view without its lean, or strafing would drift up or down
+    let basis = Transform::from_rotation(transform.rotation * Quat::from_rotation_z(-roll.0));
 
     let mut any_movement_requested = false;
     let mut wanted_move = Vec3::ZERO;
 
     // Forward is -z
     if keys.pressed(KeyCode::W) {
-        wanted_move += transform.forward();
+        wanted_move += basis.forward();
         any_movement_requested = true;
     } else if keys.pressed(KeyCode::S) {
-        wanted_move += transform.back();
+        wanted_move += basis.back();
         any_movement_requested = true;
     }
 
     // Do nothing if A and D are pressed together
     if keys.pressed(KeyCode::A) && keys.pressed(KeyCode::D) {
     } else if keys.pressed(KeyCode::D) {
-        wanted_move += transform.right();
+        wanted_move += basis.right();
         any_movement_requested = true;
     } else if keys.pressed(KeyCode::A) {
-        wanted_move += transform.left();
+        wanted_move += basis.left();
         any_movement_requested = true;
     };
 
     // Do nothing if Space and Shift are pressed together
     if keys.pressed(KeyCode::Space) && keys.pressed(KeyCode::LShift) {
     } else if keys.pressed(KeyCode::LShift) {
-        wanted_move += transform.down();
+        wanted_move += basis.down();
         any_movement_requested = true;
     } else if keys.pressed(KeyCode::Space) {
-        wanted_move += transform.up();
+        wanted_move += basis.up();
         any_movement_requested = true;
     };
 
-    let mouse_motion = mouse_evr.iter().fold(Vec2::ZERO, |acc, ev| acc + ev.delta);
-    let sensitivity = 0.0009;
-
-    transform.rotate_local_x(-mouse_motion.y * sensitivity);
-    transform.rotate_local_y(-mouse_motion.x * sensitivity);
-
     if !any_movement_requested {
         return;
     }
@@ -70,3 +130,170 @@ pub fn camera_movement_system(
     wanted_move = wanted_move.normalize();
     transform.translation += wanted_move * 0.2;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::time::FixedTimesteps;
+
+    use super::*;
+    use crate::test_support::{move_mouse, press_key, release_key, test_app, tick};
+
+    fn camera_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<InputSettings>()
+            .insert_resource(CameraRollSettings {
+                amplitude: 0.3,
+                speed: 8.,
+            })
+            .add_event::<MouseMotion>()
+            .add_system(camera_look_system)
+            .add_system(camera_movement_system.after(camera_look_system));
+        app.world.spawn((
+            FailedCamera,
+            CameraRoll::default(),
+            TransformBundle::default(),
+        ));
+        app
+    }
+
+    fn run_frames(app: &mut App, frames: u32) {
+        let start = Instant::now();
+        for frame in 0..frames {
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + Duration::from_millis(16) * frame);
+            app.update();
+        }
+    }
+
+    fn camera(app: &mut App) -> (Transform, f32) {
+        let (transform, roll) = app
+            .world
+            .query::<(&Transform, &CameraRoll)>()
+            .single(&app.world);
+        (*transform, roll.0)
+    }
+
+    #[test]
+    fn strafing_rolls_into_the_movement_and_back() {
+        let mut app = camera_app();
+
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+        run_frames(&mut app, 30);
+        assert!(camera(&mut app).1 < -0.1);
+
+        let mut keys = app.world.resource_mut::<Input<KeyCode>>();
+        keys.release(KeyCode::D);
+        keys.press(KeyCode::A);
+        run_frames(&mut app, 30);
+        assert!(camera(&mut app).1 > 0.1);
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .release(KeyCode::A);
+        run_frames(&mut app, 60);
+        assert!(camera(&mut app).1.abs() < 1e-3);
+    }
+
+    #[test]
+    fn strafing_while_rolled_stays_level() {
+        let mut app = camera_app();
+
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+        run_frames(&mut app, 30);
+        let (transform, roll) = camera(&mut app);
+
+        assert!(roll < -0.1);
+        assert!(transform.translation.x > 1.);
+        assert!(transform.translation.y.abs() < 1e-4);
+        assert!(transform.translation.z.abs() < 1e-4);
+    }
+
+    /// How far the camera moves holding W for a second of frames at the given rate.
+    fn distance_moved_in_a_second(fps: u32) -> f32 {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<FixedTimesteps>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<InputSettings>()
+            .add_event::<MouseMotion>()
+            .add_plugin(FailedCameraPlugin::default());
+        app.world.spawn((
+            FailedCamera,
+            CameraRoll::default(),
+            TransformBundle::default(),
+        ));
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::W);
+
+        let start = Instant::now();
+        for frame in 0..=fps {
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + Duration::from_secs(1) * frame / fps);
+            app.update();
+        }
+
+        camera(&mut app).0.translation.length()
+    }
+
+    #[test]
+    fn movement_per_second_does_not_depend_on_the_frame_rate() {
+        let slow = distance_moved_in_a_second(20);
+        let fast = distance_moved_in_a_second(144);
+
+        assert!(slow > 10., "{}", slow);
+        // At most a fixed step apart, depending on where the second ends between steps
+        assert!((slow - fast).abs() <= 0.2 + 1e-4, "{} vs {}", slow, fast);
+    }
+
+    #[test]
+    fn holding_w_moves_the_camera_forward() {
+        let mut app = test_app();
+        app.add_system(camera_movement_system);
+        let start = Transform::from_xyz(0., 40., 0.).looking_at(Vec3::new(1., 40., 1.), Vec3::Y);
+        let camera = app
+            .world
+            .spawn((
+                FailedCamera,
+                CameraRoll::default(),
+                TransformBundle::from(start),
+            ))
+            .id();
+
+        press_key(&mut app, KeyCode::W);
+        tick(&mut app, 5);
+        release_key(&mut app, KeyCode::W);
+        tick(&mut app, 5);
+
+        let moved = app.world.get::<Transform>(camera).unwrap().translation - start.translation;
+        assert!((moved.length() - 5. * 0.2).abs() < 1e-4);
+        assert!(moved.normalize().dot(start.forward()) > 0.999);
+    }
+
+    #[test]
+    fn moving_the_mouse_right_turns_the_camera_right() {
+        let mut app = test_app();
+        app.init_resource::<InputSettings>()
+            .init_resource::<CameraRollSettings>()
+            .add_system(camera_look_system);
+        let camera = app
+            .world
+            .spawn((
+                FailedCamera,
+                CameraRoll::default(),
+                TransformBundle::default(),
+            ))
+            .id();
+
+        move_mouse(&mut app, Vec2::new(500., 0.));
+        tick(&mut app, 1);
+
+        let forward = app.world.get::<Transform>(camera).unwrap().forward();
+        assert!(forward.x > 0.1);
+        assert!(forward.y.abs() < 1e-4);
+    }
+}
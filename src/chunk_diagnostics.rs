@@ -0,0 +1,195 @@
+use std::mem::size_of_val;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, LogDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{random_tick::RandomTickEvent, CameraChunkChanged, Chunk, ChunkMeshed, ChunkReady};
+
+pub const CHUNK_MEMORY: DiagnosticId =
+    DiagnosticId::from_u128(0x7c1f_32a4_9b0e_4d61_a8e2_5f3c_0d94_b716);
+
+/// Estimated memory held by a chunk, cached when the chunk is generated or meshed so the
+/// diagnostic does not have to walk every block and vertex each frame.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct ChunkMemoryUsage {
+    pub blocks: usize,
+    pub mesh: usize,
+}
+
+impl ChunkMemoryUsage {
+    pub fn new(chunk: &Chunk, mesh: &Mesh) -> Self {
+        Self {
+            blocks: chunk_block_bytes(chunk),
+            mesh: mesh_bytes(mesh),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.blocks + self.mesh
+    }
+}
+
+pub fn chunk_block_bytes(chunk: &Chunk) -> usize {
+    size_of_val(&chunk.blocks)
+}
+
+/// Approximate vertex and index buffer size of a mesh, i.e. what ends up on the GPU.
+pub fn mesh_bytes(mesh: &Mesh) -> usize {
+    let vertex_bytes: usize = mesh
+        .attributes()
+        .map(|(_, values)| values.get_bytes().len())
+        .sum();
+    let index_bytes = mesh.get_index_buffer_bytes().map_or(0, <[u8]>::len);
+
+    vertex_bytes + index_bytes
+}
+
+/// How many chunks were generated and meshed during the last frame.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct ChunkFrameCounts {
+    pub generated: usize,
+    pub meshed: usize,
+}
+
+/// A frame doing more chunk work than this logs a warning, to find which operation causes
+/// stutters.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ChunkFrameThresholds {
+    pub generated: usize,
+    pub meshed: usize,
+}
+
+impl Default for ChunkFrameThresholds {
+    fn default() -> Self {
+        Self {
+            generated: 4,
+            meshed: 4,
+        }
+    }
+}
+
+/// Reports the memory estimated by [`ChunkMemoryUsage`] as the [`CHUNK_MEMORY`] diagnostic,
+/// in kibibytes.
+#[derive(Default)]
+pub struct ChunkDiagnosticsPlugin {
+    /// Also log the diagnostic, and chunks being generated and meshed, to the console.
+    pub log: bool,
+}
+
+impl Plugin for ChunkDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkFrameCounts>()
+            .init_resource::<ChunkFrameThresholds>()
+            .add_startup_system(setup_diagnostics)
+            .add_system(chunk_memory_diagnostic_system)
+            .add_system_to_stage(CoreStage::Last, chunk_frame_counts_system);
+
+        if self.log {
+            app.add_plugin(LogDiagnosticsPlugin::filtered(vec![CHUNK_MEMORY]))
+                .add_system(log_chunk_events_system);
+        }
+    }
+}
+
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(CHUNK_MEMORY, "chunk_memory", 20).with_suffix("KiB"));
+}
+
+fn chunk_memory_diagnostic_system(
+    mut diagnostics: ResMut<Diagnostics>,
+    chunks: Query<&ChunkMemoryUsage>,
+) {
+    let total: usize = chunks.iter().map(ChunkMemoryUsage::total).sum();
+    diagnostics.add_measurement(CHUNK_MEMORY, || total as f64 / 1024.);
+}
+
+fn log_chunk_events_system(
+    mut chunk_ready_events: EventReader<ChunkReady>,
+    mut chunk_meshed_events: EventReader<ChunkMeshed>,
+    mut camera_chunk_events: EventReader<CameraChunkChanged>,
+    mut random_tick_events: EventReader<RandomTickEvent>,
+) {
+    for event in chunk_ready_events.iter() {
+        info!("chunk {} ready", event.coords);
+    }
+
+    for event in chunk_meshed_events.iter() {
+        info!(
+            "chunk {} meshed with {} triangles",
+            event.coords, event.triangles
+        );
+    }
+
+    for event in camera_chunk_events.iter() {
+        info!("camera moved from chunk {} to {}", event.from, event.to);
+    }
+
+    // Too frequent to show by default
+    for event in random_tick_events.iter() {
+        trace!("random tick of {} at {}", event.block, event.world_pos);
+    }
+}
+
+fn chunk_frame_counts_system(
+    mut counts: ResMut<ChunkFrameCounts>,
+    thresholds: Res<ChunkFrameThresholds>,
+    mut chunk_ready_events: EventReader<ChunkReady>,
+    mut chunk_meshed_events: EventReader<ChunkMeshed>,
+) {
+    *counts = ChunkFrameCounts {
+        generated: chunk_ready_events.iter().count(),
+        meshed: chunk_meshed_events.iter().count(),
+    };
+
+    if counts.generated > thresholds.generated {
+        warn!(
+            "generated {} chunks in one frame, more than the threshold of {}",
+            counts.generated, thresholds.generated
+        );
+    }
+
+    if counts.meshed > thresholds.meshed {
+        warn!(
+            "meshed {} chunks in one frame, more than the threshold of {}",
+            counts.meshed, thresholds.meshed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::diagnostic::DiagnosticsPlugin;
+
+    use super::*;
+    use crate::{test_support::test_app, MeshedContentHash, RequiresMeshGeneration};
+
+    #[test]
+    fn frame_counts_follow_the_chunk_work_of_each_frame() {
+        let mut app = test_app();
+        app.add_plugin(DiagnosticsPlugin)
+            .add_plugin(ChunkDiagnosticsPlugin::default());
+        let counts = |app: &App| {
+            let counts = app.world.resource::<ChunkFrameCounts>();
+            (counts.generated, counts.meshed)
+        };
+
+        // Generating a chunk meshes it right away
+        app.update();
+        assert_eq!(counts(&app), (1, 1));
+        app.update();
+        assert_eq!(counts(&app), (0, 0));
+
+        let chunk = app
+            .world
+            .query_filtered::<Entity, With<Chunk>>()
+            .single(&app.world);
+        app.world
+            .entity_mut(chunk)
+            .insert(RequiresMeshGeneration)
+            .remove::<MeshedContentHash>();
+        app.update();
+        assert_eq!(counts(&app), (0, 1));
+    }
+}
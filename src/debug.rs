@@ -0,0 +1,845 @@
+use std::collections::HashSet;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use rand::{seq::IteratorRandom, Rng};
+
+use crate::{
+    aabb::aabb_overlaps_solid,
+    raycast::raycast_chunks,
+    rng::{WorldRng, WorldSeed},
+    terrain::TerrainConfig,
+    voxel_search::nearest_air_voxel,
+    AppState, Chunk, ChunkMaterial, ChunkTriangleCount, MainCamera, MeshedContentHash,
+    RequiresMeshGeneration, CHUNK_SIZE,
+};
+
+/// Key bindings for actions that help with debugging the world.
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugKeys>()
+            .init_resource::<DebugRenderSettings>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(rebuild_all_meshes_system)
+                    .with_system(toggle_double_sided_system)
+                    .with_system(double_sided_material_system.after(toggle_double_sided_system))
+                    .with_system(teleport_to_surface_system)
+                    .with_system(teleport_to_random_surface_system)
+                    .with_system(unstick_system)
+                    .with_system(toggle_triangle_heatmap_system)
+                    .with_system(triangle_heatmap_system.after(toggle_triangle_heatmap_system))
+                    .with_system(
+                        select_chunk_material_system
+                            .after(double_sided_material_system)
+                            .after(triangle_heatmap_system),
+                    )
+                    .with_system(export_heightmap_system)
+                    .with_system(unload_all_chunks_system)
+                    .with_system(show_world_seed_system),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct DebugKeys {
+    /// Remesh every chunk, e.g. to see the effect of changed meshing settings.
+    pub rebuild_all_meshes: KeyCode,
+    pub toggle_double_sided: KeyCode,
+    /// Move the camera on top of the highest block in the column it is aimed at.
+    pub teleport_to_surface: KeyCode,
+    /// Move the camera on top of a random column of a random loaded chunk, to sample the variety
+    /// of the generated terrain.
+    pub teleport_to_random_surface: KeyCode,
+    /// Move the camera out of the terrain it has flown into, to the nearest spot it fits.
+    pub unstick: KeyCode,
+    pub toggle_triangle_heatmap: KeyCode,
+    /// Write the terrain's surface height around the camera to a grayscale image, to see the
+    /// effect of the `TerrainConfig` without flying around.
+    pub export_heightmap: KeyCode,
+    /// Despawn every chunk, freeing their meshes, to measure the memory used without any. The
+    /// world is generated again on the next frame.
+    pub unload_all_chunks: KeyCode,
+    /// Log the world seed, to share or reproduce a world. With the `clipboard` feature it is
+    /// copied to the clipboard too.
+    pub show_world_seed: KeyCode,
+}
+
+impl Default for DebugKeys {
+    fn default() -> Self {
+        Self {
+            rebuild_all_meshes: KeyCode::F5,
+            toggle_double_sided: KeyCode::F6,
+            teleport_to_surface: KeyCode::T,
+            teleport_to_random_surface: KeyCode::Y,
+            unstick: KeyCode::U,
+            toggle_triangle_heatmap: KeyCode::F7,
+            export_heightmap: KeyCode::F8,
+            unload_all_chunks: KeyCode::F9,
+            show_world_seed: KeyCode::F10,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct DebugRenderSettings {
+    /// Render the back faces of chunk meshes too. A hole that stays open is a missing face, one
+    /// that closes up is a face with the wrong winding.
+    pub double_sided_chunks: bool,
+    /// Color each chunk by its triangle count, from green for none to red for
+    /// `heatmap_max_triangles` or more, to spot chunks with pathological geometry.
+    pub triangle_heatmap: bool,
+    pub heatmap_max_triangles: usize,
+}
+
+impl Default for DebugRenderSettings {
+    fn default() -> Self {
+        Self {
+            double_sided_chunks: false,
+            triangle_heatmap: false,
+            heatmap_max_triangles: 4096,
+        }
+    }
+}
+
+fn rebuild_all_meshes_system(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    chunks: Query<Entity, With<Chunk>>,
+) {
+    if !keys.just_pressed(debug_keys.rebuild_all_meshes) {
+        return;
+    }
+
+    for entity in chunks.iter() {
+        commands
+            .entity(entity)
+            .insert(RequiresMeshGeneration)
+            .remove::<MeshedContentHash>();
+    }
+}
+
+fn toggle_double_sided_system(
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    mut settings: ResMut<DebugRenderSettings>,
+) {
+    if keys.just_pressed(debug_keys.toggle_double_sided) {
+        settings.double_sided_chunks = !settings.double_sided_chunks;
+    }
+}
+
+/// Copy of a chunk's [`ChunkMaterial`] that also draws back faces.
+#[derive(Component)]
+struct DoubleSidedMaterial(Handle<StandardMaterial>);
+
+fn double_sided(material: &StandardMaterial) -> StandardMaterial {
+    StandardMaterial {
+        double_sided: true,
+        cull_mode: None,
+        ..material.clone()
+    }
+}
+
+fn double_sided_material_system(
+    mut commands: Commands,
+    settings: Res<DebugRenderSettings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut material_events: EventReader<AssetEvent<StandardMaterial>>,
+    chunks: Query<(Entity, &ChunkMaterial, Option<&DoubleSidedMaterial>)>,
+) {
+    let modified: HashSet<_> = material_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, chunk_material, double_sided_material) in chunks.iter() {
+        match double_sided_material {
+            Some(_) if !settings.double_sided_chunks => {
+                commands.entity(entity).remove::<DoubleSidedMaterial>();
+            }
+            None if settings.double_sided_chunks => {
+                let Some(material) = materials.get(&chunk_material.0).map(double_sided) else {
+                    continue;
+                };
+                let handle = materials.add(material);
+                commands.entity(entity).insert(DoubleSidedMaterial(handle));
+            }
+            // Follow changes to the chunk's own material, e.g. from `ChunkMaterialSettings`
+            Some(double_sided_material) if modified.contains(&chunk_material.0) => {
+                if let Some(material) = materials.get(&chunk_material.0).map(double_sided) {
+                    *materials.get_mut(&double_sided_material.0).unwrap() = material;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// How far away a column may be aimed at to teleport to it.
+const TELEPORT_DISTANCE: f32 = 128.;
+
+/// Height of the camera above the block it is teleported onto.
+const TELEPORT_EYE_HEIGHT: f32 = 1.6;
+
+fn teleport_to_surface_system(
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    chunks: Query<&Chunk>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !keys.just_pressed(debug_keys.teleport_to_surface) {
+        return;
+    }
+
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+
+    let Some((chunk, hit)) = raycast_chunks(
+        chunks.iter(),
+        camera.translation,
+        camera.forward(),
+        TELEPORT_DISTANCE,
+    ) else {
+        warn!("not aiming at a loaded block, not teleporting");
+        return;
+    };
+
+    let column = chunk.origin() + hit.pos;
+    let top = surface_top(&chunks, column.x, column.z).unwrap_or(column.y);
+    teleport_onto(&mut camera, column.x, top, column.z);
+}
+
+/// How many random columns are tried before giving up on finding one with a solid block in it.
+const RANDOM_TELEPORT_ATTEMPTS: usize = 16;
+
+fn teleport_to_random_surface_system(
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    mut rng: ResMut<WorldRng>,
+    chunks: Query<&Chunk>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !keys.just_pressed(debug_keys.teleport_to_random_surface) {
+        return;
+    }
+
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+
+    for _ in 0..RANDOM_TELEPORT_ATTEMPTS {
+        let Some(chunk) = chunks.iter().choose(&mut **rng) else {
+            return;
+        };
+
+        let x = chunk.origin().x + rng.gen_range(0..CHUNK_SIZE.x);
+        let z = chunk.origin().z + rng.gen_range(0..CHUNK_SIZE.z);
+        if let Some(top) = surface_top(&chunks, x, z) {
+            teleport_onto(&mut camera, x, top, z);
+            return;
+        }
+    }
+
+    warn!("no solid column found in the loaded chunks, not teleporting");
+}
+
+/// How far away the camera may be moved to get it out of the terrain.
+const UNSTICK_RADIUS: i32 = 8;
+
+/// Half the width of the box around the camera that has to be clear of blocks.
+const UNSTICK_HALF_WIDTH: f32 = 0.3;
+
+fn unstick_system(
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    chunks: Query<&Chunk>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !keys.just_pressed(debug_keys.unstick) {
+        return;
+    }
+
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+
+    let feet_pos = camera.translation - Vec3::Y * TELEPORT_EYE_HEIGHT;
+    let half_width = Vec3::new(UNSTICK_HALF_WIDTH, 0., UNSTICK_HALF_WIDTH);
+    let head_pos = camera.translation + Vec3::Y * 0.1;
+    if !aabb_overlaps_solid(feet_pos - half_width, head_pos + half_width, chunks.iter()) {
+        return;
+    }
+
+    let feet = feet_pos.floor().as_ivec3();
+    match nearest_air_voxel(feet, UNSTICK_RADIUS, 2, chunks.iter()) {
+        Some(pos) if pos == feet => {}
+        Some(pos) => teleport_onto(&mut camera, pos.x, pos.y - 1, pos.z),
+        None => warn!(
+            "no open space within {} blocks, not unsticking",
+            UNSTICK_RADIUS
+        ),
+    }
+}
+
+/// World y of the highest solid block at `x`, `z` in any loaded chunk.
+fn surface_top(chunks: &Query<&Chunk>, x: i32, z: i32) -> Option<i32> {
+    chunks
+        .iter()
+        .filter_map(|chunk| {
+            let local = IVec3::new(x, 0, z) - chunk.origin();
+            chunk
+                .column_top(local.x, local.z)
+                .map(|y| chunk.origin().y + y)
+        })
+        .max()
+}
+
+/// Puts the camera at eye height above the block at `x`, `top`, `z`.
+fn teleport_onto(camera: &mut Transform, x: i32, top: i32, z: i32) {
+    camera.translation = Vec3::new(
+        x as f32 + 0.5,
+        (top + 1) as f32 + TELEPORT_EYE_HEIGHT,
+        z as f32 + 0.5,
+    );
+}
+
+fn toggle_triangle_heatmap_system(
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    mut settings: ResMut<DebugRenderSettings>,
+) {
+    if keys.just_pressed(debug_keys.toggle_triangle_heatmap) {
+        settings.triangle_heatmap = !settings.triangle_heatmap;
+    }
+}
+
+/// Material coloring a chunk by its triangle count.
+#[derive(Component)]
+struct HeatmapMaterial(Handle<StandardMaterial>);
+
+#[allow(clippy::type_complexity)]
+fn triangle_heatmap_system(
+    mut commands: Commands,
+    settings: Res<DebugRenderSettings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    chunks: Query<
+        (
+            Entity,
+            Option<&ChunkTriangleCount>,
+            Option<&HeatmapMaterial>,
+        ),
+        With<Chunk>,
+    >,
+) {
+    for (entity, triangle_count, heatmap_material) in chunks.iter() {
+        let triangles = triangle_count.map_or(0, |triangle_count| triangle_count.0);
+        let heat = (triangles as f32 / settings.heatmap_max_triangles as f32).min(1.);
+        let color = Color::rgb(heat, 1. - heat, 0.);
+
+        match heatmap_material {
+            Some(_) if !settings.triangle_heatmap => {
+                commands.entity(entity).remove::<HeatmapMaterial>();
+            }
+            None if settings.triangle_heatmap => {
+                let handle = materials.add(StandardMaterial {
+                    base_color: color,
+                    unlit: true,
+                    ..default()
+                });
+                commands.entity(entity).insert(HeatmapMaterial(handle));
+            }
+            // Recolored every frame, so the color follows the chunk being remeshed
+            Some(heatmap_material)
+                if materials
+                    .get(&heatmap_material.0)
+                    .is_some_and(|material| material.base_color != color) =>
+            {
+                materials.get_mut(&heatmap_material.0).unwrap().base_color = color;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Shows each chunk with the material of the debug view that is on, the heatmap taking
+/// precedence, or its own material when none are.
+#[allow(clippy::type_complexity)]
+fn select_chunk_material_system(
+    mut chunks: Query<(
+        &mut Handle<StandardMaterial>,
+        &ChunkMaterial,
+        Option<&DoubleSidedMaterial>,
+        Option<&HeatmapMaterial>,
+    )>,
+) {
+    for (mut shown, chunk_material, double_sided_material, heatmap_material) in chunks.iter_mut() {
+        let material = heatmap_material
+            .map(|material| &material.0)
+            .or(double_sided_material.map(|material| &material.0))
+            .unwrap_or(&chunk_material.0);
+
+        if *shown != *material {
+            *shown = material.clone();
+        }
+    }
+}
+
+/// Side length, in blocks and pixels, of the exported heightmap.
+const HEIGHTMAP_SIZE: u32 = 256;
+
+const HEIGHTMAP_PATH: &str = "heightmap.png";
+
+/// Samples the surface height of a `size` × `size` area centered on `center`, one column per
+/// pixel, from black at `min_height` to white at `max_height`.
+fn heightmap_image(config: &TerrainConfig, world_seed: u64, center: IVec2, size: u32) -> Image {
+    let min = center - IVec2::splat(size as i32 / 2);
+    let height_range = (config.max_height - config.min_height).max(1) as f32;
+
+    let surface = config.surface(world_seed);
+    let mut data = Vec::with_capacity((size * size) as usize);
+    for z in 0..size as i32 {
+        for x in 0..size as i32 {
+            let height = surface.height(min.x + x, min.y + z);
+            let value = (height - config.min_height) as f32 / height_range;
+            data.push((value.clamp(0., 1.) * 255.).round() as u8);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::R8Unorm,
+    )
+}
+
+fn export_heightmap_system(
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    terrain_config: Res<TerrainConfig>,
+    world_seed: Res<WorldSeed>,
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    if !keys.just_pressed(debug_keys.export_heightmap) {
+        return;
+    }
+
+    let center = cameras.get_single().map_or(IVec2::ZERO, |camera| {
+        let translation = camera.translation();
+        IVec2::new(translation.x.floor() as i32, translation.z.floor() as i32)
+    });
+
+    let image = heightmap_image(&terrain_config, world_seed.0, center, HEIGHTMAP_SIZE);
+    match image
+        .try_into_dynamic()
+        .and_then(|image| Ok(image.save(HEIGHTMAP_PATH)?))
+    {
+        Ok(()) => info!(
+            "wrote the heightmap around {} to {}",
+            center, HEIGHTMAP_PATH
+        ),
+        Err(err) => error!("failed to write the heightmap: {}", err),
+    }
+}
+
+/// Frames it takes the asset systems to free the meshes of despawned chunks.
+const MESH_FREE_FRAMES: u32 = 3;
+
+/// An unload waiting for its meshes to be freed before it is logged.
+struct PendingUnload {
+    chunks: usize,
+    meshes_before: usize,
+    frames_left: u32,
+}
+
+fn unload_all_chunks_system(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    meshes: Res<Assets<Mesh>>,
+    chunks: Query<Entity, With<Chunk>>,
+    mut pending: Local<Option<PendingUnload>>,
+) {
+    if let Some(unload) = pending.as_mut() {
+        if unload.frames_left > 0 {
+            unload.frames_left -= 1;
+        } else {
+            // The world is refilled right away, so a count back at the one before means nothing
+            // leaked
+            info!(
+                "unloaded {} chunks, {} meshes were loaded before and {} are now",
+                unload.chunks,
+                unload.meshes_before,
+                meshes.len()
+            );
+            *pending = None;
+        }
+    }
+
+    if !keys.just_pressed(debug_keys.unload_all_chunks) {
+        return;
+    }
+
+    // Chunk meshes are only held by their chunk, so they are freed along with it
+    let mut unloaded = 0;
+    for entity in chunks.iter() {
+        commands.entity(entity).despawn_recursive();
+        unloaded += 1;
+    }
+
+    *pending = Some(PendingUnload {
+        chunks: unloaded,
+        meshes_before: meshes.len(),
+        frames_left: MESH_FREE_FRAMES,
+    });
+}
+
+fn show_world_seed_system(
+    keys: Res<Input<KeyCode>>,
+    debug_keys: Res<DebugKeys>,
+    world_seed: Res<WorldSeed>,
+) {
+    if !keys.just_pressed(debug_keys.show_world_seed) {
+        return;
+    }
+
+    let seed = world_seed.to_string();
+    info!("world seed: {}", seed);
+
+    #[cfg(feature = "clipboard")]
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(seed)) {
+        Ok(()) => info!("copied the world seed to the clipboard"),
+        // No clipboard in headless or sandboxed sessions, the seed is still in the log
+        Err(err) => warn!("failed to copy the world seed to the clipboard: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::AssetPlugin, input::InputPlugin, render::render_resource::Face};
+
+    use super::*;
+    use crate::{
+        rng::WorldSeed,
+        test_support::{press_key, release_key, run_until_meshed, test_app, tick},
+        BlockType,
+    };
+
+    #[test]
+    fn rebuild_key_marks_every_chunk_for_remeshing() {
+        let mut app = App::new();
+        app.add_plugin(InputPlugin)
+            .init_resource::<DebugKeys>()
+            .add_system(rebuild_all_meshes_system);
+        let chunks: Vec<_> = (0..3)
+            .map(|x| {
+                app.world
+                    .spawn((
+                        Chunk {
+                            chunk_coords: IVec3::new(x, 0, 0),
+                            ..default()
+                        },
+                        MeshedContentHash(0),
+                    ))
+                    .id()
+            })
+            .collect();
+        let not_a_chunk = app.world.spawn(MeshedContentHash(0)).id();
+
+        let rebuild = app.world.resource::<DebugKeys>().rebuild_all_meshes;
+        press_key(&mut app, rebuild);
+        app.update();
+
+        for chunk in chunks {
+            let chunk = app.world.entity(chunk);
+            assert!(chunk.contains::<RequiresMeshGeneration>());
+            // Otherwise the unchanged blocks would skip the rebuild
+            assert!(!chunk.contains::<MeshedContentHash>());
+        }
+        assert!(!app
+            .world
+            .entity(not_a_chunk)
+            .contains::<RequiresMeshGeneration>());
+    }
+
+    #[test]
+    fn teleport_key_moves_the_camera_on_top_of_the_targeted_column() {
+        let mut app = App::new();
+        app.add_plugin(InputPlugin)
+            .init_resource::<DebugKeys>()
+            .add_system(teleport_to_surface_system);
+        let mut chunk = Chunk::default();
+        for y in [1, 2, 6] {
+            *chunk.get_block_mut(IVec3::new(5, y, 3)).unwrap() = BlockType::Stone;
+        }
+        app.world.spawn(chunk);
+        let eye = Vec3::new(0.5, 2.5, 3.5);
+        let camera = app
+            .world
+            .spawn((
+                MainCamera,
+                Transform::from_translation(eye).looking_at(eye + Vec3::X, Vec3::Y),
+            ))
+            .id();
+        let teleport = app.world.resource::<DebugKeys>().teleport_to_surface;
+
+        press_key(&mut app, teleport);
+        app.update();
+        release_key(&mut app, teleport);
+        app.update();
+        // Aimed at the block at y 2, landing on the one at y 6 above it
+        let translation = app.world.get::<Transform>(camera).unwrap().translation;
+        assert_eq!(translation, Vec3::new(5.5, 7. + TELEPORT_EYE_HEIGHT, 3.5));
+
+        // Looking up at nothing leaves the camera where it is
+        app.world
+            .get_mut::<Transform>(camera)
+            .unwrap()
+            .look_at(translation + Vec3::Y, Vec3::X);
+        press_key(&mut app, teleport);
+        app.update();
+        assert_eq!(
+            app.world.get::<Transform>(camera).unwrap().translation,
+            translation
+        );
+    }
+
+    #[test]
+    fn random_teleport_lands_on_a_solid_block_of_a_loaded_chunk() {
+        let mut app = App::new();
+        app.add_plugin(InputPlugin)
+            .init_resource::<DebugKeys>()
+            .insert_resource(WorldRng::from_seed(WorldSeed(7)))
+            .add_system(teleport_to_random_surface_system);
+        let eye = Vec3::new(0.5, 40., 0.5);
+        let camera = app
+            .world
+            .spawn((MainCamera, Transform::from_translation(eye)))
+            .id();
+        let teleport = app.world.resource::<DebugKeys>().teleport_to_random_surface;
+
+        // Nothing loaded yet, so there is nowhere to go
+        press_key(&mut app, teleport);
+        app.update();
+        release_key(&mut app, teleport);
+        app.update();
+        assert_eq!(app.world.get::<Transform>(camera).unwrap().translation, eye);
+
+        // A floor across one chunk and a single pillar in the other
+        let mut floor = Chunk::default();
+        for x in 0..CHUNK_SIZE.x {
+            for z in 0..CHUNK_SIZE.z {
+                *floor.get_block_mut(IVec3::new(x, 3, z)).unwrap() = BlockType::Stone;
+            }
+        }
+        let mut pillar = Chunk {
+            chunk_coords: IVec3::X,
+            ..default()
+        };
+        *pillar.get_block_mut(IVec3::new(2, 9, 5)).unwrap() = BlockType::Stone;
+        app.world.spawn(floor);
+        app.world.spawn(pillar);
+
+        for _ in 0..8 {
+            press_key(&mut app, teleport);
+            app.update();
+            release_key(&mut app, teleport);
+            app.update();
+
+            let translation = app.world.get::<Transform>(camera).unwrap().translation;
+            let landed_on = IVec3::new(
+                translation.x.floor() as i32,
+                (translation.y - TELEPORT_EYE_HEIGHT - 1.).round() as i32,
+                translation.z.floor() as i32,
+            );
+            let mut chunks = app.world.query::<&Chunk>();
+            let block = chunks
+                .iter(&app.world)
+                .find_map(|chunk| chunk.get_block(landed_on - chunk.origin()));
+            assert!(
+                block.is_some_and(|block| !block.is_transparent()),
+                "landed on {:?} at {}",
+                block,
+                landed_on
+            );
+            assert_eq!(translation.x.fract(), 0.5);
+            assert_eq!(translation.z.fract(), 0.5);
+        }
+    }
+
+    #[test]
+    fn heightmap_pixels_follow_the_surface_height() {
+        let config = TerrainConfig {
+            hill_height: 4,
+            ..default()
+        };
+        let (world_seed, center, size) = (42, IVec2::new(3, -5), 16);
+        let image = heightmap_image(&config, world_seed, center, size);
+
+        let extent = image.texture_descriptor.size;
+        assert_eq!((extent.width, extent.height), (size, size));
+        assert_eq!(image.data.len(), (size * size) as usize);
+
+        let min = center - IVec2::splat(size as i32 / 2);
+        let samples: Vec<_> = (0..size as i32)
+            .flat_map(|z| (0..size as i32).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let height = config.surface_height(world_seed, min.x + x, min.y + z);
+                (height, image.data[(z * size as i32 + x) as usize])
+            })
+            .collect();
+        assert!(
+            samples.iter().any(|(height, _)| *height != samples[0].0),
+            "the sampled area should not be flat"
+        );
+        for (height, pixel) in &samples {
+            for (other_height, other_pixel) in &samples {
+                if height < other_height {
+                    assert!(pixel < other_pixel, "higher ground should be brighter");
+                }
+            }
+        }
+    }
+
+    fn debug_render_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .init_resource::<DebugRenderSettings>()
+            .add_system(double_sided_material_system)
+            .add_system(triangle_heatmap_system)
+            .add_system(
+                select_chunk_material_system
+                    .after(double_sided_material_system)
+                    .after(triangle_heatmap_system),
+            );
+
+        let material = app
+            .world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default());
+        let chunk = app
+            .world
+            .spawn((
+                Chunk::default(),
+                Handle::<Mesh>::default(),
+                material.clone(),
+                ChunkMaterial(material),
+            ))
+            .id();
+
+        (app, chunk)
+    }
+
+    fn shown_material(app: &App, chunk: Entity) -> &StandardMaterial {
+        let handle = app.world.get::<Handle<StandardMaterial>>(chunk).unwrap();
+        app.world
+            .resource::<Assets<StandardMaterial>>()
+            .get(handle)
+            .unwrap()
+    }
+
+    /// Materials are swapped through commands, so a toggle shows on the frame after.
+    fn update(app: &mut App) {
+        app.update();
+        app.update();
+    }
+
+    #[test]
+    fn double_sided_toggle_swaps_in_a_material_without_culling() {
+        let (mut app, chunk) = debug_render_app();
+
+        app.world
+            .resource_mut::<DebugRenderSettings>()
+            .double_sided_chunks = true;
+        update(&mut app);
+        assert_eq!(shown_material(&app, chunk).cull_mode, None);
+
+        let chunk_material = &app.world.get::<ChunkMaterial>(chunk).unwrap().0;
+        let chunk_material = app
+            .world
+            .resource::<Assets<StandardMaterial>>()
+            .get(chunk_material)
+            .unwrap();
+        assert_eq!(chunk_material.cull_mode, Some(Face::Back));
+
+        app.world
+            .resource_mut::<DebugRenderSettings>()
+            .double_sided_chunks = false;
+        update(&mut app);
+        assert_eq!(shown_material(&app, chunk).cull_mode, Some(Face::Back));
+    }
+
+    #[test]
+    fn heatmap_and_double_sided_views_do_not_undo_each_other() {
+        let (mut app, chunk) = debug_render_app();
+
+        let mut settings = app.world.resource_mut::<DebugRenderSettings>();
+        settings.double_sided_chunks = true;
+        settings.triangle_heatmap = true;
+        update(&mut app);
+        assert!(shown_material(&app, chunk).unlit);
+
+        app.world
+            .resource_mut::<DebugRenderSettings>()
+            .triangle_heatmap = false;
+        update(&mut app);
+        let shown = shown_material(&app, chunk);
+        assert!(!shown.unlit);
+        assert_eq!(shown.cull_mode, None);
+    }
+
+    #[test]
+    fn unloaded_chunks_are_freed_and_generated_again() {
+        let mut app = test_app();
+        app.init_resource::<DebugKeys>()
+            .add_system(unload_all_chunks_system);
+        run_until_meshed(&mut app, &[IVec3::ZERO], 10).unwrap();
+        let chunk_entities = |app: &mut App| -> Vec<Entity> {
+            app.world
+                .query_filtered::<Entity, With<Chunk>>()
+                .iter(&app.world)
+                .collect()
+        };
+        let loaded = chunk_entities(&mut app);
+        let mesh_count = app.world.resource::<Assets<Mesh>>().len();
+
+        let unload = app.world.resource::<DebugKeys>().unload_all_chunks;
+        press_key(&mut app, unload);
+        app.update();
+        assert!(chunk_entities(&mut app).is_empty());
+
+        // Leave the game so the world isn't refilled while the dropped meshes are freed
+        release_key(&mut app, unload);
+        let mut state = app.world.resource_mut::<State<AppState>>();
+        state.set(AppState::AssetValidation).unwrap();
+        tick(&mut app, MESH_FREE_FRAMES as usize);
+        assert!(chunk_entities(&mut app).is_empty());
+        assert!(app.world.resource::<Assets<Mesh>>().len() < mesh_count);
+
+        let mut state = app.world.resource_mut::<State<AppState>>();
+        state.set(AppState::Game).unwrap();
+        run_until_meshed(&mut app, &[IVec3::ZERO], 10).unwrap();
+        tick(&mut app, MESH_FREE_FRAMES as usize);
+        let reloaded = chunk_entities(&mut app);
+        assert_eq!(reloaded.len(), loaded.len());
+        assert!(reloaded.iter().all(|entity| !loaded.contains(entity)));
+        assert_eq!(app.world.resource::<Assets<Mesh>>().len(), mesh_count);
+    }
+}
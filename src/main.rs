@@ -1,17 +1,46 @@
+mod aabb;
+mod block_outline;
+mod block_tooltip;
 mod camera;
-
-use std::collections::HashMap;
+mod chunk_diagnostics;
+mod debug;
+mod random_tick;
+mod raycast;
+mod rng;
+mod sky;
+mod structures;
+mod terrain;
+#[cfg(test)]
+mod test_support;
+mod voxel_search;
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt,
+    hash::{Hash, Hasher},
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
 
 use bevy::{
     asset::LoadState,
     pbr::wireframe::{Wireframe, WireframePlugin},
     prelude::*,
-    reflect::{DynamicEnum, DynamicVariant},
     render::{mesh::Indices, render_resource::PrimitiveTopology},
-    time::FixedTimestep,
+    sprite::TextureAtlasBuilderError,
     window::{close_on_esc, CursorGrabMode},
 };
-use camera::failed_camera::{FailedCameraBundle, FailedCameraPlugin};
+use block_outline::BlockOutlinePlugin;
+use block_tooltip::BlockTooltipPlugin;
+use camera::failed_camera::{CameraRoll, FailedCameraBundle, FailedCameraPlugin};
+use chunk_diagnostics::{ChunkDiagnosticsPlugin, ChunkMemoryUsage};
+use debug::DebugPlugin;
+use random_tick::RandomTickPlugin;
+use raycast::raycast_chunks;
+use rng::{WorldRng, WorldSeed};
+use sky::SkyPlugin;
+use terrain::TerrainConfig;
 
 #[derive(Hash, Clone, Debug, Eq, PartialEq)]
 enum AppState {
@@ -37,29 +66,73 @@ fn main() {
     )
     // Bevy Plugins
     .add_plugin(WireframePlugin::default())
-    // Bevy resources
-    .insert_resource(ClearColor(Color::rgb(0.2, 0.2, 0.8)))
     // Custom Plugins
     .add_plugin(FailedCameraPlugin::default())
+    .add_plugin(ChunkDiagnosticsPlugin { log: true })
+    .add_plugin(BlockOutlinePlugin)
+    .add_plugin(BlockTooltipPlugin)
+    .add_plugin(DebugPlugin)
+    .add_plugin(SkyPlugin)
+    .add_plugin(RandomTickPlugin)
+    .add_plugin(WorldPlugin)
     // Custom resources
     .init_resource::<BlockTextureHandles>()
-    .init_resource::<UvMappings>()
+    .init_resource::<TexturePackSettings>()
+    .init_resource::<GraphicsSettings>()
+    .init_resource::<InputSettings>()
     // Startup Systems
     .add_startup_system(setup_camera)
     .add_startup_system(load_textures)
     .add_startup_system(setup_world)
     .add_state(AppState::AssetValidation)
-    .add_system_set(SystemSet::new().with_run_criteria(FixedTimestep::step(TIME_STEP as f64)))
     .add_system_set(SystemSet::on_update(AppState::AssetValidation).with_system(validate_textures))
-    .add_system_set(SystemSet::on_enter(AppState::Game).with_system(generate_chunk))
     .add_system(close_on_esc)
     .add_system(cursor_lock_system)
+    .add_system(apply_graphics_settings)
+    .add_system(fit_sun_shadows_system)
     .run();
 }
 
+/// Generates the world and keeps chunk meshes up to date once the game has started. Leaves
+/// rendering and texture loading to the app, so it also runs headless.
+struct WorldPlugin;
+
+impl Plugin for WorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UvMappings>()
+            .init_resource::<ChunkMeshSettings>()
+            .init_resource::<ChunkMaterialSettings>()
+            .init_resource::<TerrainConfig>()
+            .init_resource::<WorldSeed>()
+            .init_resource::<WorldRng>()
+            .add_event::<ChunkReady>()
+            .add_event::<ChunkMeshed>()
+            .add_event::<CameraChunkChanged>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(generate_chunk)
+                    .with_system(remesh_on_settings_change_system)
+                    .with_system(apply_chunk_material_settings)
+                    .with_system(mesh_generation_system)
+                    .with_system(chunk_fade_in_system)
+                    .with_system(camera_chunk_changed_system),
+            );
+    }
+}
+
 #[derive(Deref, DerefMut, Resource, Default)]
 struct BlockTextureHandles(Vec<HandleUntyped>);
 
+/// Where block textures are loaded from, relative to the assets folder.
+const BLOCK_TEXTURES_FOLDER: &str = "textures/blocks";
+
+#[derive(Resource, Clone, Debug, Default)]
+struct TexturePackSettings {
+    /// A folder laid out like the built-in block textures, relative to the assets folder, e.g.
+    /// `resource_packs/my_pack`. Its textures replace built-in ones with the same block and name.
+    override_folder: Option<String>,
+}
+
 #[derive(Resource, DerefMut, Deref, Clone)]
 struct BlockAtlasHandle(Handle<TextureAtlas>);
 
@@ -69,8 +142,31 @@ struct BlockAtlasHandle(Handle<TextureAtlas>);
 // struct BlockTextureAtlas(TextureAtlas);
 
 type UVs = [[f32; 2]; 4];
+
+const NULL_UV: UVs = [[0., 0.], [0., 0.], [0., 0.], [0., 0.]];
+
+#[derive(Clone, Copy, Debug)]
+struct BlockUvs {
+    top: UVs,
+    side: UVs,
+    bottom: UVs,
+    /// Drawn on top of the side faces and tinted, like the grass strip on a grass block.
+    side_overlay: Option<UVs>,
+}
+
+impl Default for BlockUvs {
+    fn default() -> Self {
+        Self {
+            top: NULL_UV,
+            side: NULL_UV,
+            bottom: NULL_UV,
+            side_overlay: None,
+        }
+    }
+}
+
 #[derive(Resource, Deref, DerefMut, Default, Debug)]
-struct UvMappings(HashMap<BlockType, (UVs, UVs, UVs)>);
+struct UvMappings(HashMap<BlockType, BlockUvs>);
 
 fn construct_atlas(
     mut commands: Commands,
@@ -79,32 +175,65 @@ fn construct_atlas(
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut textures: ResMut<Assets<Image>>,
     mut uv_mappings: ResMut<UvMappings>,
+    texture_pack_settings: Res<TexturePackSettings>,
 ) {
-    let mut texture_atlas_builder = TextureAtlasBuilder::default();
+    let mut handles = Vec::new();
     for handle in block_texture_handles.iter() {
         let handle = handle.typed_weak();
-        let Some(texture) = textures.get(&handle) else {
-            panic!("{:?} did not resolve to an `Image` asset.", asset_server.get_handle_path(handle));
-        };
+        if textures.get(&handle).is_none() {
+            panic!(
+                "{:?} did not resolve to an `Image` asset.",
+                asset_server.get_handle_path(handle)
+            );
+        }
 
-        texture_atlas_builder.add_texture(handle, texture);
+        handles.push(handle);
     }
 
-    let texture_atlas = texture_atlas_builder.finish(&mut textures).unwrap();
+    let placeholder_folders: Vec<_> = std::iter::once(BLOCK_TEXTURES_FOLDER)
+        .chain(texture_pack_settings.override_folder.as_deref())
+        .map(|folder| Path::new(folder).join(BlockType::Placeholder.to_string()))
+        .collect();
+    let placeholder_handles: Vec<_> = handles
+        .iter()
+        .filter(|handle| {
+            asset_server.get_handle_path(*handle).is_some_and(|path| {
+                placeholder_folders
+                    .iter()
+                    .any(|folder| path.path().starts_with(folder))
+            })
+        })
+        .cloned()
+        .collect();
+
+    let (texture_atlas, is_fallback) = atlas_or_placeholder_fallback(
+        pack_block_textures(&handles, &mut textures),
+        &placeholder_handles,
+        &mut textures,
+    );
 
-    let mut textures: HashMap<String, (UVs, UVs, UVs)> = HashMap::new();
+    let mut textures: HashMap<String, BlockUvs> = HashMap::new();
     for handle in block_texture_handles.iter() {
         if let Some(handle_path) = asset_server.get_handle_path(handle) {
-            let handle_path = handle_path.path().strip_prefix("textures/blocks");
+            let override_path = texture_pack_settings
+                .override_folder
+                .as_ref()
+                .and_then(|folder| handle_path.path().strip_prefix(folder).ok());
+            let is_override = override_path.is_some();
+
+            let handle_path = override_path
+                .ok_or(())
+                .or_else(|_| handle_path.path().strip_prefix(BLOCK_TEXTURES_FOLDER));
             if handle_path.is_err() {
                 warn!("something funky is goinng on in the texture loading");
                 continue;
             }
             let handle_path = handle_path.unwrap();
 
-            let texture_idx = texture_atlas
-                .get_texture_index(&handle.typed_weak())
-                .unwrap();
+            // Textures left out of a fallback atlas
+            let Some(texture_idx) = texture_atlas.get_texture_index(&handle.typed_weak()) else {
+                continue;
+            };
 
             let texture_uvs = {
                 let atlas_size = texture_atlas.size;
@@ -148,59 +277,33 @@ fn construct_atlas(
                 .to_string_lossy()
                 .to_string();
 
-            if texture_position.as_str() == "texture" {
-                textures.insert(
-                    block_name,
-                    (texture_uvs.clone(), texture_uvs.clone(), texture_uvs),
+            // Override textures are loaded after the built-in ones, so they are assigned last
+            if is_override {
+                info!(
+                    "using the override {} texture for {}",
+                    texture_position, block_name
                 );
-            } else {
-                const NULL_UV: [[f32; 2]; 4] = [[0., 0.], [0., 0.], [0., 0.], [0., 0.]];
-
-                textures
-                    .entry(block_name.clone())
-                    .or_insert((NULL_UV, NULL_UV, NULL_UV));
-                println!("tex_pos {:?}, block {:?}", texture_position, block_name);
-                match texture_position.as_str() {
-                    "top" => {
-                        let (top, _, _) = textures.get_mut(&block_name).unwrap();
-                        *top = texture_uvs;
-                    }
-                    "side" => {
-                        let (_, side, _) = textures.get_mut(&block_name).unwrap();
-                        *side = texture_uvs;
-                    }
-                    "bottom" => {
-                        let (_, _, bottom) = textures.get_mut(&block_name).unwrap();
-                        *bottom = texture_uvs;
-                    }
-                    _ => {
-                        panic!("Some bad input to the texture loading")
-                    }
+            }
+
+            let block_uvs = textures.entry(block_name).or_default();
+            match texture_position.as_str() {
+                "texture" => {
+                    block_uvs.top = texture_uvs;
+                    block_uvs.side = texture_uvs;
+                    block_uvs.bottom = texture_uvs;
+                }
+                "top" => block_uvs.top = texture_uvs,
+                "side" => block_uvs.side = texture_uvs,
+                "bottom" => block_uvs.bottom = texture_uvs,
+                "side_overlay" => block_uvs.side_overlay = Some(texture_uvs),
+                _ => {
+                    panic!("Some bad input to the texture loading")
                 }
             }
         }
     }
 
-    {
-        let placeholder = textures.remove("Placeholder");
-        uv_mappings
-            .0
-            .insert(BlockType::Placeholder, placeholder.unwrap());
-    }
-
-    // let reflect = BlockType::as_reflect(&BlockType::Air).;
-    for entry in textures {
-        let mut curr_type = BlockType::Air;
-        let dynamic_enum = DynamicEnum::new(
-            Reflect::type_name(&BlockType::Air),
-            &entry.0,
-            DynamicVariant::Unit,
-        );
-
-        curr_type.apply(&dynamic_enum);
-
-        (*uv_mappings).insert(curr_type, entry.1);
-    }
+    insert_block_uvs(&mut uv_mappings, textures, is_fallback);
 
     println!("{:#?}", *uv_mappings);
 
@@ -208,6 +311,67 @@ fn construct_atlas(
     commands.remove_resource::<BlockTextureHandles>();
 }
 
+fn pack_block_textures(
+    handles: &[Handle<Image>],
+    textures: &mut Assets<Image>,
+) -> Result<TextureAtlas, TextureAtlasBuilderError> {
+    let mut texture_atlas_builder = TextureAtlasBuilder::default();
+    for handle in handles {
+        texture_atlas_builder.add_texture(handle.clone(), textures.get(handle).unwrap());
+    }
+
+    texture_atlas_builder.finish(textures)
+}
+
+/// The packed atlas, or if packing failed one with only `placeholder_handles`, so the game still
+/// launches with every block drawn using the placeholder texture. Also returns whether it fell
+/// back.
+fn atlas_or_placeholder_fallback(
+    packed: Result<TextureAtlas, TextureAtlasBuilderError>,
+    placeholder_handles: &[Handle<Image>],
+    textures: &mut Assets<Image>,
+) -> (TextureAtlas, bool) {
+    match packed {
+        Ok(texture_atlas) => (texture_atlas, false),
+        Err(err) => {
+            error!(
+                "failed to pack the block textures into an atlas, falling back to the placeholder textures: {:?}",
+                err
+            );
+            let texture_atlas = pack_block_textures(placeholder_handles, textures)
+                .expect("the placeholder textures alone should fit in an atlas");
+            (texture_atlas, true)
+        }
+    }
+}
+
+/// Maps each block to its textures by block name, `textures` must include the placeholder's. In a
+/// fallback atlas every block without textures of its own uses the placeholder.
+fn insert_block_uvs(
+    uv_mappings: &mut UvMappings,
+    mut textures: HashMap<String, BlockUvs>,
+    is_fallback: bool,
+) {
+    let placeholder = textures.remove("Placeholder").unwrap();
+    uv_mappings.insert(BlockType::Placeholder, placeholder);
+
+    if is_fallback {
+        for block_type in BlockType::ALL {
+            uv_mappings.entry(block_type).or_insert(placeholder);
+        }
+    }
+
+    for (block_name, uvs) in textures {
+        match block_name.parse::<BlockType>() {
+            Ok(block_type) => {
+                uv_mappings.insert(block_type, uvs);
+            }
+            Err(err) => warn!("skipping block textures: {}", err),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn validate_textures(
     mut state: ResMut<State<AppState>>,
     asset_server: Res<AssetServer>,
@@ -216,6 +380,7 @@ fn validate_textures(
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut textures: ResMut<Assets<Image>>,
     uv_mappings: ResMut<UvMappings>,
+    texture_pack_settings: Res<TexturePackSettings>,
 ) {
     // println!("IN Validate textures");
 
@@ -231,6 +396,7 @@ fn validate_textures(
             texture_atlases,
             textures,
             uv_mappings,
+            texture_pack_settings,
         );
     }
 }
@@ -238,24 +404,137 @@ fn validate_textures(
 fn load_textures(
     asset_server: Res<AssetServer>,
     mut block_texture_handles: ResMut<BlockTextureHandles>,
+    texture_pack_settings: Res<TexturePackSettings>,
 ) {
-    block_texture_handles.0 = asset_server.load_folder("./textures/blocks").unwrap();
+    block_texture_handles.0 = asset_server.load_folder(BLOCK_TEXTURES_FOLDER).unwrap();
+
+    if let Some(folder) = &texture_pack_settings.override_folder {
+        match asset_server.load_folder(folder) {
+            Ok(handles) => block_texture_handles.0.extend(handles),
+            Err(err) => warn!("skipping override block textures in {}: {}", folder, err),
+        }
+    }
 }
 
 #[derive(Component)]
 struct MainCamera;
 
-fn setup_camera(mut commands: Commands) {
-    commands.spawn(DirectionalLightBundle {
-        transform: Transform::from_xyz(-5., 5., 5.).looking_at(Vec3::new(0., 0., 0.), Vec3::Y),
-        directional_light: DirectionalLight {
-            color: Color::WHITE,
-            illuminance: 50_000.,
-            shadows_enabled: true,
-            ..default()
-        },
-        ..default()
+#[derive(Component)]
+struct Sun;
+
+#[derive(Resource, Clone, Debug)]
+struct GraphicsSettings {
+    /// Real-time shadows cast by the sun, covering every loaded chunk. Expensive, turn them off
+    /// on slower machines.
+    shadows: bool,
+    /// Grow newly spawned chunks up out of the ground over this long, instead of popping in.
+    chunk_fade_in: Option<Duration>,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            shadows: true,
+            chunk_fade_in: None,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+struct InputSettings {
+    /// Scales both sensitivities, to adjust the overall mouse speed in one place.
+    sensitivity: f32,
+    /// Radians of yaw per unit of horizontal mouse motion.
+    sensitivity_x: f32,
+    /// Radians of pitch per unit of vertical mouse motion.
+    sensitivity_y: f32,
+    /// Toggles whether the cursor is locked to the window. Clicking the window also locks it.
+    cursor_lock_key: KeyCode,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.,
+            sensitivity_x: 0.0009,
+            sensitivity_y: 0.0009,
+            cursor_lock_key: KeyCode::Return,
+        }
+    }
+}
+
+impl InputSettings {
+    /// Yaw (x) and pitch (y), in radians, to turn by for the given mouse motion.
+    fn look_delta(&self, mouse_motion: Vec2) -> Vec2 {
+        mouse_motion * Vec2::new(self.sensitivity_x, self.sensitivity_y) * self.sensitivity
+    }
+}
+
+fn apply_graphics_settings(
+    settings: Res<GraphicsSettings>,
+    mut suns: Query<&mut DirectionalLight, With<Sun>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for mut light in suns.iter_mut() {
+        light.shadows_enabled = settings.shadows;
+    }
+}
+
+/// Fits the sun's shadow map around the loaded chunks whenever they change, so shadows reach as
+/// far as the world is loaded and no further.
+fn fit_sun_shadows_system(
+    chunks: Query<&Chunk>,
+    mut suns: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+    mut fitted_bounds: Local<Option<(IVec3, IVec3)>>,
+) {
+    let bounds = chunks.iter().fold(None, |bounds, chunk| {
+        let (min, max) = (chunk.origin(), chunk.origin() + CHUNK_SIZE);
+        Some(
+            bounds.map_or((min, max), |(bounds_min, bounds_max): (IVec3, IVec3)| {
+                (bounds_min.min(min), bounds_max.max(max))
+            }),
+        )
     });
+    if bounds.is_none() || bounds == *fitted_bounds {
+        return;
+    }
+    *fitted_bounds = bounds;
+    let (min, max) = bounds.unwrap();
+
+    // Any direction the sun shines from fits the sphere around the loaded area
+    let center = (min + max).as_vec3() / 2.;
+    let radius = (max - min).as_vec3().length() / 2.;
+    for (mut light, mut transform) in suns.iter_mut() {
+        // Only the sun's direction lights the world, its position only places the shadow map
+        transform.translation = center;
+        light.shadow_projection = OrthographicProjection {
+            left: -radius,
+            right: radius,
+            bottom: -radius,
+            top: radius,
+            near: -radius,
+            far: radius,
+            ..default()
+        };
+    }
+}
+
+fn setup_camera(mut commands: Commands, graphics_settings: Res<GraphicsSettings>) {
+    commands
+        .spawn(DirectionalLightBundle {
+            transform: Transform::from_xyz(-5., 5., 5.).looking_at(Vec3::new(0., 0., 0.), Vec3::Y),
+            directional_light: DirectionalLight {
+                color: Color::WHITE,
+                illuminance: 50_000.,
+                shadows_enabled: graphics_settings.shadows,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Sun);
 
     commands
         .spawn(FailedCameraBundle {
@@ -264,6 +543,7 @@ fn setup_camera(mut commands: Commands) {
                 ..default()
             },
             failed_camera: camera::failed_camera::FailedCamera,
+            roll: CameraRoll(0.),
         })
         .insert(MainCamera);
 }
@@ -278,16 +558,22 @@ struct Chunk {
 }
 
 impl Chunk {
-    fn generate_terrain(mut self) -> Self {
-        for (pos, block) in self.iter_blocks_mut() {
-            if pos.y <= 5 {
-                *block = BlockType::Placeholder
-            };
-        }
+    /// Fills the chunk from the world seed, see [`TerrainConfig::generate_region`].
+    fn generate_terrain(mut self, config: &TerrainConfig) -> Self {
+        let origin = self.origin();
+        config.generate_region(
+            self.world_seed,
+            origin,
+            CHUNK_SIZE,
+            &mut |world_pos, block| {
+                *self.get_block_mut(world_pos - origin).unwrap() = block;
+            },
+        );
 
         self
     }
 
+    #[cfg(test)]
     fn iter_blocks_mut(&mut self) -> impl Iterator<Item = (IVec3, &mut BlockType)> {
         self.blocks
             .iter_mut()
@@ -318,6 +604,23 @@ impl Chunk {
             })
     }
 
+    /// Hash of the chunk's blocks, identical for chunks that would build identical meshes.
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.blocks.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// World position of the block at local position zero.
+    fn origin(&self) -> IVec3 {
+        self.chunk_coords * CHUNK_SIZE
+    }
+
+    /// Coordinates of the chunk containing the world position `pos`.
+    fn world_coord_chunk(pos: Vec3) -> IVec3 {
+        (pos / CHUNK_SIZE.as_vec3()).floor().as_ivec3()
+    }
+
     fn get_block(&self, pos: IVec3) -> Option<&BlockType> {
         if !Chunk::is_within_bounds(pos) {
             return None;
@@ -344,40 +647,86 @@ impl Chunk {
             && pos.z < CHUNK_SIZE.z;
     }
 
-    fn construct_mesh(&self, uv_mappings: &UvMappings) -> Mesh {
-        let mut indicies = Vec::new();
-        let mut vertecies = Vec::new();
+    /// Local y of the topmost solid block in the column at `x`, `z`, if it has any.
+    fn column_top(&self, x: i32, z: i32) -> Option<i32> {
+        (0..CHUNK_SIZE.y).rev().find(|&y| {
+            self.get_block(IVec3::new(x, y, z))
+                .is_some_and(|block| !block.is_transparent())
+        })
+    }
+
+    /// Ambient occlusion of the face of the block at `pos` facing `normal`, for each of the given
+    /// corners of the face.
+    fn face_occlusion(
+        &self,
+        pos: IVec3,
+        normal: IVec3,
+        corners: &[[f32; 3]; 4],
+        level: AmbientOcclusion,
+    ) -> FaceOcclusion {
+        if level == AmbientOcclusion::Off {
+            return FaceOcclusion::NONE;
+        }
 
-        let mut uvs: Vec<[f32; 2]> = Vec::new();
+        let is_solid = |offset: IVec3| matches!(self.get_block(pos + normal + offset), Some(block) if !block.is_transparent());
 
-        for (pos, block) in self.iter_blocks() {
-            if self.get_block(pos) == Some(&BlockType::Air) {
-                continue;
+        let center = pos.as_vec3() + Vec3::splat(0.5);
+        let brightness = corners.map(|corner| {
+            // Points from the block's center towards the corner, with each component ±1
+            let direction = ((Vec3::from(corner) - center) * 2.).round().as_ivec3();
+            let tangent = direction - normal * direction.dot(normal);
+
+            let mut sides = [IVec3::ZERO; 2];
+            for (side, axis) in sides
+                .iter_mut()
+                .zip((0..3).filter(|&axis| tangent[axis] != 0))
+            {
+                side[axis] = tangent[axis];
             }
 
-            let top_visible = self
-                .get_block(pos + IVec3::Y)
-                .map_or(true, |block| block.is_transparent());
+            let side_a = is_solid(sides[0]);
+            let side_b = is_solid(sides[1]);
+            let unoccluded = match level {
+                AmbientOcclusion::Full if side_a && side_b => 0,
+                AmbientOcclusion::Full => {
+                    3 - side_a as usize - side_b as usize - is_solid(tangent) as usize
+                }
+                _ => 3 - side_a as usize - side_b as usize,
+            };
+
+            FaceOcclusion::BRIGHTNESS[unoccluded]
+        });
+
+        FaceOcclusion {
+            brightness,
+            flip: level == AmbientOcclusion::Full
+                && brightness[1] + brightness[3] > brightness[0] + brightness[2],
+        }
+    }
 
-            let front_visible = self
-                .get_block(pos + IVec3::Z)
-                .map_or(true, |block| block.is_transparent());
+    fn construct_mesh(&self, uv_mappings: &UvMappings, settings: &ChunkMeshSettings) -> Mesh {
+        let mut mesh = ChunkMeshBuilder::default();
 
-            let right_visible = self
-                .get_block(pos + IVec3::X)
-                .map_or(true, |block| block.is_transparent());
+        for (pos, block) in self.iter_blocks() {
+            if self.get_block(pos) == Some(&BlockType::Air) {
+                continue;
+            }
 
-            let back_visible = self
-                .get_block(pos + IVec3::NEG_Z)
-                .map_or(true, |block| block.is_transparent());
+            let block_uvs = uv_mappings.get(block).expect("Texture not found");
+            let overlay_tint = settings.overlay_tint(block);
 
-            let left_visible = self
-                .get_block(pos + IVec3::NEG_X)
-                .map_or(true, |block| block.is_transparent());
+            // Faces on the chunk border are always drawn
+            let face_visible = |normal: IVec3| {
+                self.get_block(pos + normal)
+                    .is_none_or(|neighbor| should_render_face(block, neighbor))
+            };
 
-            let bottom_visible = self
-                .get_block(pos + IVec3::NEG_Y)
-                .map_or(true, |block| block.is_transparent());
+            let top_visible = face_visible(IVec3::Y);
+            let front_visible = face_visible(IVec3::Z);
+            let right_visible = face_visible(IVec3::X);
+            let back_visible = face_visible(IVec3::NEG_Z);
+            let left_visible = face_visible(IVec3::NEG_X);
+            let bottom_visible = face_visible(IVec3::NEG_Y);
 
             if !top_visible
                 && !front_visible
@@ -402,141 +751,157 @@ impl Chunk {
             // ]);
 
             if top_visible {
-                let vertex_offset = vertecies.len() as u32;
-                vertecies.extend_from_slice(&[
+                let corners = [
                     [fpos.x, fpos.y + 1., fpos.z],
                     [fpos.x + 1., fpos.y + 1., fpos.z],
                     [fpos.x + 1., fpos.y + 1., fpos.z + 1.],
                     [fpos.x, fpos.y + 1., fpos.z + 1.],
-                ]);
-
-                indicies.extend_from_slice(&[
-                    vertex_offset + 3,
-                    vertex_offset + 1,
-                    vertex_offset + 0,
-                    // Second triangle
-                    vertex_offset + 3,
-                    vertex_offset + 2,
-                    vertex_offset + 1,
-                ]);
-
-                let uv = &uv_mappings.get(block).expect("Texture not found").0;
-                uvs.extend_from_slice(uv);
-            };
+                ];
+                let shade = settings.face_shade(BlockFace::Top);
+                let occlusion =
+                    self.face_occlusion(pos, IVec3::Y, &corners, settings.ambient_occlusion);
+                mesh.push_quad(
+                    corners,
+                    Vec3::Y,
+                    &block_uvs.top,
+                    Color::WHITE * shade,
+                    occlusion,
+                );
+            }
 
             if front_visible {
-                let vertex_offset = vertecies.len() as u32;
-                vertecies.extend_from_slice(&[
+                let corners = [
                     [fpos.x, fpos.y + 1., fpos.z + 1.],
                     [fpos.x + 1., fpos.y + 1., fpos.z + 1.],
                     [fpos.x + 1., fpos.y, fpos.z + 1.],
                     [fpos.x, fpos.y, fpos.z + 1.],
-                ]);
-
-                indicies.extend_from_slice(&[
-                    vertex_offset + 3,
-                    vertex_offset + 1,
-                    vertex_offset + 0,
-                    // Second triangle
-                    vertex_offset + 3,
-                    vertex_offset + 2,
-                    vertex_offset + 1,
-                ]);
-
-                let uv = &uv_mappings.get(block).expect("Texture not found").1;
-                uvs.extend_from_slice(uv);
+                ];
+                let shade = settings.face_shade(BlockFace::Front);
+                let occlusion =
+                    self.face_occlusion(pos, IVec3::Z, &corners, settings.ambient_occlusion);
+                mesh.push_quad(
+                    corners,
+                    Vec3::Z,
+                    &block_uvs.side,
+                    Color::WHITE * shade,
+                    occlusion,
+                );
+
+                if let Some(overlay) = &block_uvs.side_overlay {
+                    mesh.push_overlay_quad(
+                        corners,
+                        Vec3::Z,
+                        overlay,
+                        overlay_tint * shade,
+                        occlusion,
+                    );
+                }
             }
 
             if right_visible {
-                let vertex_offset = vertecies.len() as u32;
-                vertecies.extend_from_slice(&[
+                let corners = [
                     [fpos.x + 1., fpos.y + 1., fpos.z + 1.],
                     [fpos.x + 1., fpos.y + 1., fpos.z],
                     [fpos.x + 1., fpos.y, fpos.z],
                     [fpos.x + 1., fpos.y, fpos.z + 1.],
-                ]);
-
-                indicies.extend_from_slice(&[
-                    vertex_offset + 3,
-                    vertex_offset + 1,
-                    vertex_offset + 0,
-                    // Second triangle
-                    vertex_offset + 3,
-                    vertex_offset + 2,
-                    vertex_offset + 1,
-                ]);
-
-                let uv = &uv_mappings.get(block).expect("Texture not found").1;
-                uvs.extend_from_slice(uv);
+                ];
+                let shade = settings.face_shade(BlockFace::Right);
+                let occlusion =
+                    self.face_occlusion(pos, IVec3::X, &corners, settings.ambient_occlusion);
+                mesh.push_quad(
+                    corners,
+                    Vec3::X,
+                    &block_uvs.side,
+                    Color::WHITE * shade,
+                    occlusion,
+                );
+
+                if let Some(overlay) = &block_uvs.side_overlay {
+                    mesh.push_overlay_quad(
+                        corners,
+                        Vec3::X,
+                        overlay,
+                        overlay_tint * shade,
+                        occlusion,
+                    );
+                }
             }
 
             if back_visible {
-                let vertex_offset = vertecies.len() as u32;
-                vertecies.extend_from_slice(&[
+                let corners = [
                     [fpos.x + 1., fpos.y + 1., fpos.z],
                     [fpos.x, fpos.y + 1., fpos.z],
                     [fpos.x, fpos.y, fpos.z],
                     [fpos.x + 1., fpos.y, fpos.z],
-                ]);
-
-                indicies.extend_from_slice(&[
-                    vertex_offset + 3,
-                    vertex_offset + 1,
-                    vertex_offset + 0,
-                    // Second triangle
-                    vertex_offset + 3,
-                    vertex_offset + 2,
-                    vertex_offset + 1,
-                ]);
-
-                let uv = &uv_mappings.get(block).expect("Texture not found").1;
-                uvs.extend_from_slice(uv);
+                ];
+                let shade = settings.face_shade(BlockFace::Back);
+                let occlusion =
+                    self.face_occlusion(pos, IVec3::NEG_Z, &corners, settings.ambient_occlusion);
+                mesh.push_quad(
+                    corners,
+                    Vec3::NEG_Z,
+                    &block_uvs.side,
+                    Color::WHITE * shade,
+                    occlusion,
+                );
+
+                if let Some(overlay) = &block_uvs.side_overlay {
+                    mesh.push_overlay_quad(
+                        corners,
+                        Vec3::NEG_Z,
+                        overlay,
+                        overlay_tint * shade,
+                        occlusion,
+                    );
+                }
             }
 
             if left_visible {
-                let vertex_offset = vertecies.len() as u32;
-                vertecies.extend_from_slice(&[
+                let corners = [
                     [fpos.x, fpos.y + 1., fpos.z],
                     [fpos.x, fpos.y + 1., fpos.z + 1.],
                     [fpos.x, fpos.y, fpos.z + 1.],
                     [fpos.x, fpos.y, fpos.z],
-                ]);
-
-                indicies.extend_from_slice(&[
-                    vertex_offset + 3,
-                    vertex_offset + 1,
-                    vertex_offset + 0,
-                    // Second triangle
-                    vertex_offset + 3,
-                    vertex_offset + 2,
-                    vertex_offset + 1,
-                ]);
-
-                let uv = &uv_mappings.get(block).expect("Texture not found").1;
-                uvs.extend_from_slice(uv);
+                ];
+                let shade = settings.face_shade(BlockFace::Left);
+                let occlusion =
+                    self.face_occlusion(pos, IVec3::NEG_X, &corners, settings.ambient_occlusion);
+                mesh.push_quad(
+                    corners,
+                    Vec3::NEG_X,
+                    &block_uvs.side,
+                    Color::WHITE * shade,
+                    occlusion,
+                );
+
+                if let Some(overlay) = &block_uvs.side_overlay {
+                    mesh.push_overlay_quad(
+                        corners,
+                        Vec3::NEG_X,
+                        overlay,
+                        overlay_tint * shade,
+                        occlusion,
+                    );
+                }
             }
 
             if bottom_visible {
-                let vertex_offset = vertecies.len() as u32;
-                vertecies.extend_from_slice(&[
+                let corners = [
                     [fpos.x, fpos.y, fpos.z + 1.],
                     [fpos.x + 1., fpos.y, fpos.z + 1.],
                     [fpos.x + 1., fpos.y, fpos.z],
                     [fpos.x, fpos.y, fpos.z],
-                ]);
-
-                indicies.extend_from_slice(&[
-                    vertex_offset + 3,
-                    vertex_offset + 1,
-                    vertex_offset + 0,
-                    // Second triangle
-                    vertex_offset + 3,
-                    vertex_offset + 2,
-                    vertex_offset + 1,
-                ]);
-
-                let uv = &uv_mappings.get(block).expect("Texture not found").2;
-                uvs.extend_from_slice(uv);
+                ];
+                let shade = settings.face_shade(BlockFace::Bottom);
+                let occlusion =
+                    self.face_occlusion(pos, IVec3::NEG_Y, &corners, settings.ambient_occlusion);
+                mesh.push_quad(
+                    corners,
+                    Vec3::NEG_Y,
+                    &block_uvs.bottom,
+                    Color::WHITE * shade,
+                    occlusion,
+                );
             }
 
             //             if front_visible {
@@ -605,11 +970,273 @@ impl Chunk {
             //             }
         }
 
+        mesh.build()
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+struct ChunkMeshSettings {
+    /// Color multiplied onto side overlays, e.g. the grass strip on the side of grass blocks.
+    grass_tint: Color,
+    /// Brightness of each face direction, a cheap stand-in for lighting that gives terrain depth.
+    face_shading: FaceShading,
+    /// How many chunks `mesh_generation_system` may remesh in a single frame.
+    max_meshes_per_frame: usize,
+    /// Remesh the chunk the camera is in and the one it is aimed at before any other, then the
+    /// rest nearest first, so the area being looked at is never stale.
+    prioritize_nearby_chunks: bool,
+    /// How much sooner the remaining chunks ahead of the camera are remeshed than those beside
+    /// or behind it, from 0 for purely by distance to 1 for a chunk straight ahead counting as
+    /// right next to the camera.
+    look_direction_weight: f32,
+    ambient_occlusion: AmbientOcclusion,
+}
+
+/// Darkens face corners next to other blocks. Higher levels look better but mesh slower.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum AmbientOcclusion {
+    Off,
+    /// Only considers the two blocks sharing an edge with a corner.
+    Fast,
+    /// Also considers the diagonal block, and flips quads to hide interpolation artifacts.
+    #[default]
+    Full,
+}
+
+/// Brightness of each corner of a quad, in the order its corners were given.
+#[derive(Clone, Copy, Debug)]
+struct FaceOcclusion {
+    brightness: [f32; 4],
+    /// Split the quad along the other diagonal so brightness interpolates symmetrically.
+    flip: bool,
+}
+
+impl FaceOcclusion {
+    const NONE: FaceOcclusion = FaceOcclusion {
+        brightness: [1.; 4],
+        flip: false,
+    };
+
+    /// Brightness by number of unoccluded neighbours.
+    const BRIGHTNESS: [f32; 4] = [0.45, 0.6, 0.8, 1.];
+}
+
+impl Default for ChunkMeshSettings {
+    fn default() -> Self {
+        Self {
+            grass_tint: Color::rgb_u8(0x2a, 0x9d, 0x38),
+            face_shading: FaceShading::default(),
+            max_meshes_per_frame: 4,
+            prioritize_nearby_chunks: true,
+            look_direction_weight: 0.5,
+            ambient_occlusion: AmbientOcclusion::default(),
+        }
+    }
+}
+
+/// Applied to the material shared by the chunk meshes, without remeshing them.
+#[derive(Resource, Clone, Debug)]
+struct ChunkMaterialSettings {
+    /// Multiplied onto the block textures.
+    base_color: Color,
+    /// Skip lighting and rely on the shading baked into the mesh colors.
+    unlit: bool,
+}
+
+impl Default for ChunkMaterialSettings {
+    fn default() -> Self {
+        Self {
+            base_color: Color::WHITE,
+            unlit: true,
+        }
+    }
+}
+
+impl ChunkMaterialSettings {
+    fn apply(&self, material: &mut StandardMaterial) {
+        material.base_color = self.base_color;
+        material.unlit = self.unlit;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct FaceShading {
+    top: f32,
+    front_back: f32,
+    left_right: f32,
+    bottom: f32,
+}
+
+impl Default for FaceShading {
+    fn default() -> Self {
+        Self {
+            top: 1.,
+            front_back: 0.8,
+            left_right: 0.65,
+            bottom: 0.5,
+        }
+    }
+}
+
+impl ChunkMeshSettings {
+    fn face_shade(&self, face: BlockFace) -> f32 {
+        let shading = &self.face_shading;
+        match face {
+            BlockFace::Top => shading.top,
+            BlockFace::Front | BlockFace::Back => shading.front_back,
+            BlockFace::Right | BlockFace::Left => shading.left_right,
+            BlockFace::Bottom => shading.bottom,
+        }
+    }
+
+    fn overlay_tint(&self, block: &BlockType) -> Color {
+        match block {
+            BlockType::Grass => self.grass_tint,
+            _ => Color::WHITE,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ChunkMeshBuilder {
+    indices: Vec<u32>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+}
+
+impl ChunkMeshBuilder {
+    /// How far overlay quads are pushed out from the face they cover, to avoid z-fighting.
+    const OVERLAY_OFFSET: f32 = 0.001;
+
+    /// Pushes a quad given its corners in the same order as the `UVs` they map to.
+    fn push_quad(
+        &mut self,
+        corners: [[f32; 3]; 4],
+        normal: Vec3,
+        uvs: &UVs,
+        color: Color,
+        occlusion: FaceOcclusion,
+    ) {
+        let vertex_offset = self.positions.len() as u32;
+        self.positions.extend_from_slice(&corners);
+        self.normals.extend_from_slice(&[normal.to_array(); 4]);
+
+        if occlusion.flip {
+            self.indices.extend_from_slice(&[
+                vertex_offset + 2,
+                vertex_offset + 1,
+                vertex_offset,
+                // Second triangle
+                vertex_offset + 3,
+                vertex_offset + 2,
+                vertex_offset,
+            ]);
+        } else {
+            self.indices.extend_from_slice(&[
+                vertex_offset + 3,
+                vertex_offset + 1,
+                vertex_offset,
+                // Second triangle
+                vertex_offset + 3,
+                vertex_offset + 2,
+                vertex_offset + 1,
+            ]);
+        }
+
+        self.uvs.extend_from_slice(uvs);
+        self.colors.extend(
+            occlusion
+                .brightness
+                .map(|brightness| (color * brightness).as_linear_rgba_f32()),
+        );
+    }
+
+    fn push_overlay_quad(
+        &mut self,
+        corners: [[f32; 3]; 4],
+        normal: Vec3,
+        uvs: &UVs,
+        color: Color,
+        occlusion: FaceOcclusion,
+    ) {
+        let offset = normal * Self::OVERLAY_OFFSET;
+        let corners = corners.map(|corner| (Vec3::from(corner) + offset).to_array());
+        self.push_quad(corners, normal, uvs, color, occlusion);
+    }
+
+    /// Checks the invariants the renderer relies on, which a meshing bug would silently break.
+    fn validate(&self) -> Result<(), String> {
+        let vertex_count = self.positions.len();
+
+        if self.normals.len() != vertex_count {
+            return Err(format!(
+                "{} normals for {} vertices",
+                self.normals.len(),
+                vertex_count
+            ));
+        }
+
+        if self.uvs.len() != vertex_count {
+            return Err(format!(
+                "{} uvs for {} vertices",
+                self.uvs.len(),
+                vertex_count
+            ));
+        }
+
+        if self.colors.len() != vertex_count {
+            return Err(format!(
+                "{} colors for {} vertices",
+                self.colors.len(),
+                vertex_count
+            ));
+        }
+
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(format!(
+                "{} indices do not form triangles",
+                self.indices.len()
+            ));
+        }
+
+        if let Some(index) = self
+            .indices
+            .iter()
+            .find(|&&index| index as usize >= vertex_count)
+        {
+            return Err(format!(
+                "index {} out of bounds for {} vertices",
+                index, vertex_count
+            ));
+        }
+
+        if let Some(position) = self
+            .positions
+            .iter()
+            .find(|position| !Vec3::from(**position).is_finite())
+        {
+            return Err(format!("non-finite vertex position {:?}", position));
+        }
+
+        Ok(())
+    }
+
+    fn build(self) -> Mesh {
+        if cfg!(debug_assertions) {
+            if let Err(err) = self.validate() {
+                panic!("Constructed an invalid chunk mesh: {}", err);
+            }
+        }
+
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
-        mesh.set_indices(Some(Indices::U32(indicies)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertecies);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
         mesh
     }
 }
@@ -626,6 +1253,7 @@ struct BlocksAtlas(TextureAtlas);
 //     // asset_server.add_loader();
 // }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum BlockFace {
     Top,
     Front,
@@ -635,12 +1263,28 @@ enum BlockFace {
     Bottom,
 }
 
-#[derive(Default, PartialEq, Debug, Reflect, Eq, PartialOrd, Ord, Hash)]
+/// Whether the face of `current` that touches `neighbor` can be seen. Every face culling decision
+/// goes through here, so blocks that only partially hide their neighbours need a rule of their own.
+fn should_render_face(current: &BlockType, neighbor: &BlockType) -> bool {
+    match (current, neighbor) {
+        (BlockType::Air, _) => false,
+        (_, BlockType::Air) => true,
+        // Opaque blocks hide each other
+        (
+            BlockType::Grass | BlockType::Stone | BlockType::Bedrock | BlockType::Placeholder,
+            BlockType::Grass | BlockType::Stone | BlockType::Bedrock | BlockType::Placeholder,
+        ) => false,
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Debug, Reflect, Eq, PartialOrd, Ord, Hash)]
 enum BlockType {
     #[default]
     Air,
     Grass,
     Stone,
+    /// Can't be generated away, so the world always has a bottom.
+    Bedrock,
     Placeholder,
 }
 
@@ -648,7 +1292,52 @@ struct BlockData {
     is_transparent: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseBlockTypeError(String);
+
+impl fmt::Display for ParseBlockTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown block type {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseBlockTypeError {}
+
+impl fmt::Display for BlockType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BlockType::Air => "Air",
+            BlockType::Grass => "Grass",
+            BlockType::Stone => "Stone",
+            BlockType::Bedrock => "Bedrock",
+            BlockType::Placeholder => "Placeholder",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for BlockType {
+    type Err = ParseBlockTypeError;
+
+    /// Parses a block name case-insensitively, e.g. "grass", "Stone" or "AIR".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BlockType::ALL
+            .into_iter()
+            .find(|block_type| block_type.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseBlockTypeError(s.to_string()))
+    }
+}
+
 impl BlockType {
+    const ALL: [BlockType; 5] = [
+        BlockType::Air,
+        BlockType::Grass,
+        BlockType::Stone,
+        BlockType::Bedrock,
+        BlockType::Placeholder,
+    ];
+
     fn is_transparent(&self) -> bool {
         return *self == BlockType::Air;
     }
@@ -710,63 +1399,340 @@ struct ChunkBundle {
     pbr: PbrBundle,
 }
 
-fn generate_chunk(
-    mut commands: Commands,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    uv_mappings: Res<UvMappings>,
-    asset_server: Res<AssetServer>,
-    atlas_handle: Res<BlockAtlasHandle>,
-    texture_atlases: Res<Assets<TextureAtlas>>,
-) {
-    // println!(
-    //     "{:#?}",
-    //     asset_server.get_load_state(block_textures.0.clone())
-    // );
+/// Sent when a chunk has been generated and spawned.
+#[derive(Clone, Copy, Debug)]
+struct ChunkReady {
+    coords: IVec3,
+}
 
-    println!("Generating chunk");
-    let chunk_coords = IVec3::ZERO;
-    let world_seed = 0u64;
+/// Sent whenever a chunk's mesh has been (re)built.
+#[derive(Clone, Copy, Debug)]
+struct ChunkMeshed {
+    coords: IVec3,
+    triangles: usize,
+}
 
-    let chunk = Chunk {
-        world_seed,
-        ..default()
+impl ChunkMeshed {
+    fn new(chunk: &Chunk, mesh: &Mesh) -> Self {
+        Self {
+            coords: chunk.chunk_coords,
+            triangles: ChunkTriangleCount::of(mesh).0,
+        }
     }
-    .generate_terrain();
-
-    let mesh = chunk.construct_mesh(&uv_mappings);
+}
 
-    let atlas_image_handle = texture_atlases.get(&atlas_handle).unwrap().texture.clone();
+/// Sent when the camera crosses into another chunk, for work that only needs redoing when the
+/// camera's surroundings change rather than every frame.
+#[derive(Clone, Copy, Debug)]
+struct CameraChunkChanged {
+    from: IVec3,
+    to: IVec3,
+}
 
-    let material = materials.add(StandardMaterial {
-        base_color: Color::ORANGE,
-        base_color_texture: Some(atlas_image_handle),
-        unlit: true,
-        ..default()
-    });
+fn camera_chunk_changed_system(
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+    mut current_chunk: Local<Option<IVec3>>,
+    mut chunk_changed_events: EventWriter<CameraChunkChanged>,
+) {
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
 
-    commands.spawn(PbrBundle {
-        material: material.clone(),
-        mesh: meshes.add(shape::Quad::new(Vec2::new(10., 10.)).into()),
-        ..Default::default()
+    let chunk = Chunk::world_coord_chunk(camera.translation());
+    if let Some(from) = current_chunk.replace(chunk) {
+        if from != chunk {
+            chunk_changed_events.send(CameraChunkChanged { from, to: chunk });
+        }
+    }
+}
+
+/// Animates a newly spawned chunk in. Only its transform is scaled, the chunk's blocks are in
+/// place and can be targeted right away.
+#[derive(Component)]
+struct ChunkFadeIn(Timer);
+
+impl ChunkFadeIn {
+    /// Vertical scale the chunk is drawn at. Never quite zero, to keep the transform invertible.
+    fn scale(&self) -> f32 {
+        // Ease out, so the chunk settles gently
+        let progress = 1. - (1. - self.0.percent()).powi(2);
+        progress.max(0.01)
+    }
+
+    /// Transform of a chunk whose blocks start at `origin`, growing from its center rather than
+    /// from the world origin.
+    fn transform(&self, origin: Vec3) -> Transform {
+        let scale = Vec3::new(1., self.scale(), 1.);
+        let center = CHUNK_SIZE.as_vec3() / 2.;
+        Transform::from_translation(origin + center - center * scale).with_scale(scale)
+    }
+}
+
+fn chunk_fade_in_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut chunks: Query<(Entity, &Chunk, &mut Transform, &mut ChunkFadeIn)>,
+) {
+    for (entity, chunk, mut transform, mut fade_in) in chunks.iter_mut() {
+        fade_in.0.tick(time.delta());
+
+        *transform = fade_in.transform(chunk.origin().as_vec3());
+
+        if fade_in.0.finished() {
+            commands.entity(entity).remove::<ChunkFadeIn>();
+        }
+    }
+}
+
+/// Marks a chunk whose mesh is out of date with its blocks or the meshing settings.
+#[derive(Component)]
+struct RequiresMeshGeneration;
+
+/// [`Chunk::content_hash`] of the blocks the chunk's current mesh was built from. Remeshing a
+/// chunk whose blocks still hash the same is skipped, remove this to force a rebuild.
+#[derive(Component)]
+struct MeshedContentHash(u64);
+
+/// Triangles in the chunk's current mesh, recorded when it is built so debug views don't have to
+/// look through the mesh.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+struct ChunkTriangleCount(usize);
+
+impl ChunkTriangleCount {
+    fn of(mesh: &Mesh) -> Self {
+        Self(mesh.indices().map_or(0, |indices| indices.len() / 3))
+    }
+}
+
+fn remesh_on_settings_change_system(
+    mut commands: Commands,
+    mesh_settings: Res<ChunkMeshSettings>,
+    chunks: Query<Entity, With<Chunk>>,
+) {
+    if !mesh_settings.is_changed() || mesh_settings.is_added() {
+        return;
+    }
+
+    for entity in chunks.iter() {
+        commands
+            .entity(entity)
+            .insert(RequiresMeshGeneration)
+            .remove::<MeshedContentHash>();
+    }
+}
+
+/// The material a chunk is normally drawn with. Debug views swap the chunk's
+/// `Handle<StandardMaterial>` for one of their own and back, this one stays untouched.
+#[derive(Component)]
+struct ChunkMaterial(Handle<StandardMaterial>);
+
+fn apply_chunk_material_settings(
+    material_settings: Res<ChunkMaterialSettings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    chunks: Query<&ChunkMaterial>,
+) {
+    if !material_settings.is_changed() || material_settings.is_added() {
+        return;
+    }
+
+    for chunk_material in chunks.iter() {
+        if let Some(material) = materials.get_mut(&chunk_material.0) {
+            material_settings.apply(material);
+        }
+    }
+}
+
+/// How far away the chunk under the crosshair is still remeshed ahead of others.
+const MESH_PRIORITY_TARGET_DISTANCE: f32 = 64.;
+
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
+fn mesh_generation_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    uv_mappings: Res<UvMappings>,
+    mesh_settings: Res<ChunkMeshSettings>,
+    mut chunks: Query<
+        (
+            Entity,
+            &Chunk,
+            &mut Handle<Mesh>,
+            Option<&mut ChunkMemoryUsage>,
+            Option<&MeshedContentHash>,
+        ),
+        With<RequiresMeshGeneration>,
+    >,
+    all_chunks: Query<&Chunk>,
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+    mut chunk_meshed_events: EventWriter<ChunkMeshed>,
+) {
+    let camera = cameras
+        .get_single()
+        .ok()
+        .filter(|_| mesh_settings.prioritize_nearby_chunks)
+        .map(GlobalTransform::compute_transform);
+
+    // Up to date chunks block the view too, so aim through all of them
+    let targeted_chunk = camera.and_then(|camera| {
+        raycast_chunks(
+            all_chunks.iter(),
+            camera.translation,
+            camera.forward(),
+            MESH_PRIORITY_TARGET_DISTANCE,
+        )
+        .map(|(chunk, _)| chunk.chunk_coords)
     });
 
+    let mut dirty_chunks: Vec<_> = chunks.iter_mut().collect();
+
+    if let Some(camera) = camera {
+        let camera_chunk = Chunk::world_coord_chunk(camera.translation);
+        let priority = |chunk: &Chunk| {
+            let rank = if chunk.chunk_coords == camera_chunk {
+                0
+            } else if Some(chunk.chunk_coords) == targeted_chunk {
+                1
+            } else {
+                2
+            };
+            let offset = chunk.origin().as_vec3() + CHUNK_SIZE.as_vec3() / 2. - camera.translation;
+            let alignment = offset.normalize_or_zero().dot(camera.forward());
+            let weight = mesh_settings.look_direction_weight.clamp(0., 1.);
+            (rank, offset.length() * (1. - weight * alignment))
+        };
+
+        dirty_chunks.sort_by(|(_, a, ..), (_, b, ..)| {
+            let (a_rank, a_distance) = priority(a);
+            let (b_rank, b_distance) = priority(b);
+            a_rank.cmp(&b_rank).then(a_distance.total_cmp(&b_distance))
+        });
+    }
+
+    let mut meshed = 0;
+    for (entity, chunk, mut mesh_handle, memory_usage, meshed_hash) in dirty_chunks {
+        if meshed == mesh_settings.max_meshes_per_frame {
+            break;
+        }
+
+        // Only hashed once it's this chunk's turn, hashing every waiting chunk each frame would
+        // cost more than the meshing the budget allows
+        let content_hash = chunk.content_hash();
+        if meshed_hash.is_some_and(|meshed_hash| meshed_hash.0 == content_hash) {
+            // The blocks were changed back or rewritten with the same values, the mesh is still
+            // up to date
+            commands.entity(entity).remove::<RequiresMeshGeneration>();
+            continue;
+        }
+
+        meshed += 1;
+        let mesh = chunk.construct_mesh(&uv_mappings, &mesh_settings);
+        if let Some(mut memory_usage) = memory_usage {
+            *memory_usage = ChunkMemoryUsage::new(chunk, &mesh);
+        }
+        chunk_meshed_events.send(ChunkMeshed::new(chunk, &mesh));
+        let triangle_count = ChunkTriangleCount::of(&mesh);
+        *mesh_handle = meshes.add(mesh);
+
+        commands
+            .entity(entity)
+            .remove::<RequiresMeshGeneration>()
+            .insert((MeshedContentHash(content_hash), triangle_count));
+    }
+}
+
+/// Generates the world whenever it has no chunks, at the start of the game and after they were
+/// all unloaded.
+#[allow(clippy::too_many_arguments)]
+fn generate_chunk(
+    mut commands: Commands,
+    chunks: Query<(), With<Chunk>>,
+    mut ground_spawned: Local<bool>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    uv_mappings: Res<UvMappings>,
+    mesh_settings: Res<ChunkMeshSettings>,
+    material_settings: Res<ChunkMaterialSettings>,
+    terrain_config: Res<TerrainConfig>,
+    world_seed: Res<WorldSeed>,
+    graphics_settings: Res<GraphicsSettings>,
+    atlas_handle: Res<BlockAtlasHandle>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut chunk_ready_events: EventWriter<ChunkReady>,
+    mut chunk_meshed_events: EventWriter<ChunkMeshed>,
+) {
+    // println!(
+    //     "{:#?}",
+    //     asset_server.get_load_state(block_textures.0.clone())
+    // );
+
+    if !chunks.is_empty() {
+        return;
+    }
+
+    println!("Generating chunk");
+    let chunk_coords = IVec3::ZERO;
+    let world_seed = world_seed.0;
+
+    let chunk = Chunk {
+        chunk_coords,
+        world_seed,
+        ..default()
+    }
+    .generate_terrain(&terrain_config);
+
+    let mesh = chunk.construct_mesh(&uv_mappings, &mesh_settings);
+    let memory_usage = ChunkMemoryUsage::new(&chunk, &mesh);
+    let content_hash = MeshedContentHash(chunk.content_hash());
+    let triangle_count = ChunkTriangleCount::of(&mesh);
+    chunk_meshed_events.send(ChunkMeshed::new(&chunk, &mesh));
+
+    let atlas_image_handle = texture_atlases.get(&atlas_handle).unwrap().texture.clone();
+
+    let mut material = StandardMaterial {
+        base_color_texture: Some(atlas_image_handle),
+        // Cut out the transparent parts of overlay textures
+        alpha_mode: AlphaMode::Mask(0.5),
+        ..default()
+    };
+    material_settings.apply(&mut material);
+    let material = materials.add(material);
+
+    if !*ground_spawned {
+        commands.spawn(PbrBundle {
+            material: material.clone(),
+            mesh: meshes.add(shape::Quad::new(Vec2::new(10., 10.)).into()),
+            ..Default::default()
+        });
+        *ground_spawned = true;
+    }
+
     let pbr_bundle = PbrBundle {
-        material,
+        material: material.clone(),
         mesh: meshes.add(mesh),
         ..default()
     };
 
+    let chunk_origin = chunk.origin().as_vec3();
     let chunk_bundle = ChunkBundle {
         pbr: pbr_bundle,
-        chunk: Chunk {
-            chunk_coords,
-            world_seed,
-            ..default()
-        },
+        chunk,
     };
 
-    commands.spawn(chunk_bundle).insert(Wireframe);
+    let mut chunk_entity = commands.spawn(chunk_bundle);
+    chunk_entity.insert((
+        memory_usage,
+        content_hash,
+        triangle_count,
+        ChunkMaterial(material),
+        Wireframe,
+    ));
+    if let Some(duration) = graphics_settings.chunk_fade_in {
+        let fade_in = ChunkFadeIn(Timer::new(duration, TimerMode::Once));
+        chunk_entity.insert((fade_in.transform(chunk_origin), fade_in));
+    }
+    chunk_ready_events.send(ChunkReady {
+        coords: chunk_coords,
+    });
 }
 
 fn setup_world(
@@ -790,27 +1756,1100 @@ fn setup_world(
     // });
 }
 
-fn cursor_lock_system(mut windows: ResMut<Windows>, keys: Res<Input<KeyCode>>) {
-    fn toggle_mouse_lock(window: &mut Window) {
-        match window.cursor_grab_mode() {
-            CursorGrabMode::Locked => {
-                window.set_cursor_grab_mode(CursorGrabMode::None);
-                window.set_cursor_visibility(true);
+fn cursor_lock_system(
+    mut windows: ResMut<Windows>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    input_settings: Res<InputSettings>,
+) {
+    fn set_mouse_lock(window: &mut Window, locked: bool) {
+        if locked {
+            window.set_cursor_grab_mode(CursorGrabMode::Locked);
+            window.set_cursor_visibility(false);
+        } else {
+            window.set_cursor_grab_mode(CursorGrabMode::None);
+            window.set_cursor_visibility(true);
+        }
+    }
+
+    let window = windows.primary_mut();
+    let locked = window.cursor_grab_mode() == CursorGrabMode::Locked;
+
+    if !window.is_focused() {
+        if locked {
+            set_mouse_lock(window, false);
+        }
+        return;
+    }
+
+    if keys.just_pressed(input_settings.cursor_lock_key) {
+        set_mouse_lock(window, !locked);
+    } else if !locked && mouse_buttons.just_pressed(MouseButton::Left) {
+        set_mouse_lock(window, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use bevy::{
+        ecs::event::ManualEventReader,
+        render::{
+            mesh::VertexAttributeValues,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+        window::WindowId,
+    };
+
+    use crate::test_support::{
+        press_key, press_mouse_button, release_key, release_mouse_button, run_until_meshed,
+        test_app, test_uv_mappings, tick,
+    };
+
+    fn chunk_with(blocks: &[(IVec3, BlockType)]) -> Chunk {
+        let mut chunk = Chunk::default();
+        for (pos, block) in blocks {
+            *chunk.get_block_mut(*pos).unwrap() = *block;
+        }
+        chunk
+    }
+
+    /// Colors of the vertices of every face pointing along `normal`.
+    fn face_colors(mesh: &Mesh, normal: Vec3) -> Vec<[f32; 4]> {
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("chunk mesh without normals");
+        };
+        let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+        else {
+            panic!("chunk mesh without colors");
+        };
+
+        normals
+            .iter()
+            .zip(colors)
+            .filter(|(vertex_normal, _)| Vec3::from(**vertex_normal) == normal)
+            .map(|(_, color)| *color)
+            .collect()
+    }
+
+    #[test]
+    fn block_type_names_round_trip() {
+        for block_type in BlockType::ALL {
+            let name = block_type.to_string();
+            assert_eq!(name.parse::<BlockType>(), Ok(block_type));
+            assert_eq!(name.to_lowercase().parse::<BlockType>(), Ok(block_type));
+            assert_eq!(name.to_uppercase().parse::<BlockType>(), Ok(block_type));
+        }
+    }
+
+    #[test]
+    fn unknown_block_type_is_rejected() {
+        assert_eq!(
+            "dirtt".parse::<BlockType>(),
+            Err(ParseBlockTypeError("dirtt".to_string()))
+        );
+        assert!("".parse::<BlockType>().is_err());
+    }
+
+    #[test]
+    fn shadows_are_on_by_default() {
+        assert!(GraphicsSettings::default().shadows);
+    }
+
+    /// Whether every corner of the chunk at `chunk_coords` lies within the sun's shadow map.
+    fn shadow_map_covers(app: &App, sun: Entity, chunk_coords: IVec3) -> bool {
+        let projection = &app
+            .world
+            .get::<DirectionalLight>(sun)
+            .unwrap()
+            .shadow_projection;
+        let view = app
+            .world
+            .get::<Transform>(sun)
+            .unwrap()
+            .compute_matrix()
+            .inverse();
+        (0..8).all(|corner| {
+            let offset = IVec3::new(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let corner = ((chunk_coords + offset) * CHUNK_SIZE).as_vec3();
+            // Views look along -z
+            let in_view = view.transform_point3(corner);
+            (projection.left..=projection.right).contains(&in_view.x)
+                && (projection.bottom..=projection.top).contains(&in_view.y)
+                && (projection.near..=projection.far).contains(&-in_view.z)
+        })
+    }
+
+    #[test]
+    fn sun_shadows_cover_every_loaded_chunk() {
+        let mut app = App::new();
+        app.add_system(fit_sun_shadows_system);
+        let sun = app
+            .world
+            .spawn((
+                DirectionalLight::default(),
+                Transform::from_xyz(-5., 5., 5.).looking_at(Vec3::ZERO, Vec3::Y),
+                Sun,
+            ))
+            .id();
+        app.world.spawn(Chunk::default());
+        app.update();
+        assert!(shadow_map_covers(&app, sun, IVec3::ZERO));
+        let far = IVec3::new(3, 1, -4);
+        assert!(!shadow_map_covers(&app, sun, far));
+
+        // Loading more chunks grows the shadow map along with them
+        for chunk_coords in [far, IVec3::new(-2, 0, 4)] {
+            app.world.spawn(Chunk {
+                chunk_coords,
+                ..default()
+            });
+        }
+        app.update();
+        for chunk_coords in [IVec3::ZERO, far, IVec3::new(-2, 0, 4)] {
+            assert!(
+                shadow_map_covers(&app, sun, chunk_coords),
+                "chunk {} is outside the shadow map",
+                chunk_coords
+            );
+        }
+    }
+
+    #[test]
+    fn shadow_setting_toggles_sun_shadows() {
+        let mut app = App::new();
+        app.init_resource::<GraphicsSettings>()
+            .add_system(apply_graphics_settings);
+        let sun = app.world.spawn((DirectionalLight::default(), Sun)).id();
+
+        for shadows in [false, true] {
+            app.world.resource_mut::<GraphicsSettings>().shadows = shadows;
+            app.update();
+
+            let light = app.world.get::<DirectionalLight>(sun).unwrap();
+            assert_eq!(light.shadows_enabled, shadows);
+        }
+    }
+
+    #[test]
+    fn look_sensitivity_is_separate_for_yaw_and_pitch() {
+        let settings = InputSettings {
+            sensitivity: 2.,
+            sensitivity_x: 0.003,
+            sensitivity_y: 0.001,
+            ..default()
+        };
+        let look_delta = settings.look_delta(Vec2::splat(100.));
+
+        assert!((look_delta.x - 0.6).abs() < 1e-6, "{}", look_delta);
+        assert!((look_delta.y - 0.2).abs() < 1e-6, "{}", look_delta);
+        assert!((look_delta.x / look_delta.y - 3.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn top_faces_are_brighter_than_bottom_faces() {
+        let chunk = chunk_with(&[(IVec3::new(1, 1, 1), BlockType::Stone)]);
+        let settings = ChunkMeshSettings {
+            ambient_occlusion: AmbientOcclusion::Off,
+            ..default()
+        };
+        let mesh = chunk.construct_mesh(&test_uv_mappings(), &settings);
+
+        let top = face_colors(&mesh, Vec3::Y);
+        let bottom = face_colors(&mesh, Vec3::NEG_Y);
+        assert_eq!((top.len(), bottom.len()), (4, 4));
+        for (top, bottom) in top.iter().zip(&bottom) {
+            assert!(
+                top[0] > bottom[0],
+                "top {:?} not above bottom {:?}",
+                top,
+                bottom
+            );
+        }
+    }
+
+    #[test]
+    fn grass_sides_get_an_overlay_quad() {
+        let overlay = [[0., 0.], [0.5, 0.], [0.5, 0.5], [0., 0.5]];
+        let mut uv_mappings = test_uv_mappings();
+        uv_mappings.get_mut(&BlockType::Grass).unwrap().side_overlay = Some(overlay);
+        let chunk = chunk_with(&[
+            (IVec3::new(1, 1, 1), BlockType::Grass),
+            (IVec3::new(4, 1, 1), BlockType::Stone),
+        ]);
+        let mesh = chunk.construct_mesh(&uv_mappings, &ChunkMeshSettings::default());
+
+        // Both blocks have one quad per face, grass another over each of its sides
+        for side in [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z] {
+            assert_eq!(face_colors(&mesh, side).len(), 4 * 3, "{}", side);
+        }
+        for top_or_bottom in [Vec3::Y, Vec3::NEG_Y] {
+            assert_eq!(face_colors(&mesh, top_or_bottom).len(), 4 * 2);
+        }
+
+        let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+        else {
+            panic!("chunk mesh without uvs");
+        };
+        assert_eq!(uvs.chunks(4).filter(|quad| *quad == overlay).count(), 4);
+    }
+
+    fn single_quad_builder() -> ChunkMeshBuilder {
+        let mut builder = ChunkMeshBuilder::default();
+        builder.push_quad(
+            [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            Vec3::NEG_Z,
+            &[[0., 0.], [1., 0.], [1., 1.], [0., 1.]],
+            Color::WHITE,
+            FaceOcclusion::NONE,
+        );
+        builder
+    }
+
+    #[test]
+    fn well_formed_mesh_passes_validation() {
+        assert_eq!(single_quad_builder().validate(), Ok(()));
+    }
+
+    #[test]
+    fn malformed_meshes_fail_validation() {
+        let mut out_of_bounds = single_quad_builder();
+        out_of_bounds.indices.extend_from_slice(&[0, 1, 4]);
+        assert!(out_of_bounds.validate().is_err());
+
+        let mut missing_uv = single_quad_builder();
+        missing_uv.uvs.pop();
+        assert!(missing_uv.validate().is_err());
+
+        let mut not_triangles = single_quad_builder();
+        not_triangles.indices.pop();
+        assert!(not_triangles.validate().is_err());
+
+        let mut nan_position = single_quad_builder();
+        nan_position.positions[2][1] = f32::NAN;
+        assert!(nan_position.validate().is_err());
+    }
+
+    /// A block with another one diagonally above it, shading the top face's +x corners.
+    fn overhang_top_colors(ambient_occlusion: AmbientOcclusion) -> Vec<[f32; 4]> {
+        let chunk = chunk_with(&[
+            (IVec3::new(2, 1, 2), BlockType::Stone),
+            (IVec3::new(3, 2, 2), BlockType::Stone),
+        ]);
+        let settings = ChunkMeshSettings {
+            ambient_occlusion,
+            ..default()
+        };
+        let mesh = chunk.construct_mesh(&test_uv_mappings(), &settings);
+
+        // The overhanging block's own top face is unoccluded and comes second
+        face_colors(&mesh, Vec3::Y)[..4].to_vec()
+    }
+
+    #[test]
+    fn ambient_occlusion_off_gives_uniform_colors() {
+        let colors = overhang_top_colors(AmbientOcclusion::Off);
+        assert!(
+            colors.iter().all(|color| *color == colors[0]),
+            "{:?}",
+            colors
+        );
+    }
+
+    #[test]
+    fn full_ambient_occlusion_darkens_corners_under_an_overhang() {
+        let colors = overhang_top_colors(AmbientOcclusion::Full);
+        let brightest = colors.iter().map(|color| color[0]).fold(0., f32::max);
+        let darkest = colors.iter().map(|color| color[0]).fold(1., f32::min);
+        assert!(darkest < brightest, "{:?}", colors);
+    }
+
+    #[test]
+    fn fast_ambient_occlusion_only_counts_edge_neighbors_and_never_flips() {
+        let pos = IVec3::new(2, 1, 2);
+        let top = pos.as_vec3() + Vec3::Y;
+        let corners = [
+            top.to_array(),
+            (top + Vec3::X).to_array(),
+            (top + Vec3::X + Vec3::Z).to_array(),
+            (top + Vec3::Z).to_array(),
+        ];
+        let occlusion = |above: &[IVec3], level| {
+            let mut blocks = vec![(pos, BlockType::Stone)];
+            blocks.extend(above.iter().map(|offset| (pos + *offset, BlockType::Stone)));
+            chunk_with(&blocks).face_occlusion(pos, IVec3::Y, &corners, level)
+        };
+        let [one_side, unoccluded] = [2, 3].map(|sides| FaceOcclusion::BRIGHTNESS[sides]);
+
+        // A block diagonally above only shades the corner with Full
+        let diagonal = [IVec3::new(1, 1, 1)];
+        assert_eq!(
+            occlusion(&diagonal, AmbientOcclusion::Fast).brightness,
+            [unoccluded; 4]
+        );
+        assert!(occlusion(&diagonal, AmbientOcclusion::Full).brightness[2] < unoccluded);
+
+        let edge = [IVec3::new(1, 1, 0)];
+        assert_eq!(
+            occlusion(&edge, AmbientOcclusion::Fast).brightness,
+            [unoccluded, one_side, one_side, unoccluded]
+        );
+
+        // Full flips this quad to hide its dark corner's interpolation, Fast never does
+        let two_edges = [IVec3::new(-1, 1, 0), IVec3::new(0, 1, -1)];
+        assert!(occlusion(&two_edges, AmbientOcclusion::Full).flip);
+        let fast = occlusion(&two_edges, AmbientOcclusion::Fast);
+        assert!(!fast.flip);
+        assert_eq!(
+            fast.brightness,
+            [FaceOcclusion::BRIGHTNESS[1], one_side, unoccluded, one_side]
+        );
+    }
+
+    #[test]
+    fn generating_a_chunk_sends_one_ready_and_one_meshed_event() {
+        let mut app = test_app();
+        let mut ready_reader = ManualEventReader::<ChunkReady>::default();
+        let mut meshed_reader = ManualEventReader::<ChunkMeshed>::default();
+        let mut drain = |app: &App| {
+            let ready: Vec<_> = ready_reader
+                .iter(app.world.resource::<Events<ChunkReady>>())
+                .map(|event| event.coords)
+                .collect();
+            let meshed: Vec<_> = meshed_reader
+                .iter(app.world.resource::<Events<ChunkMeshed>>())
+                .copied()
+                .collect();
+            (ready, meshed)
+        };
+
+        // Events only last two updates, so they are read after each one
+        tick(&mut app, 1);
+        let (ready, meshed) = drain(&app);
+        assert_eq!(ready, vec![IVec3::ZERO]);
+        assert_eq!(meshed.len(), 1);
+        assert_eq!(meshed[0].coords, IVec3::ZERO);
+        assert!(meshed[0].triangles > 0);
+
+        tick(&mut app, 1);
+        let (ready, meshed) = drain(&app);
+        assert!(ready.is_empty() && meshed.is_empty());
+
+        // Remeshing the chunk only sends another meshed event
+        let chunk = app
+            .world
+            .query_filtered::<Entity, With<Chunk>>()
+            .single(&app.world);
+        app.world
+            .entity_mut(chunk)
+            .insert(RequiresMeshGeneration)
+            .remove::<MeshedContentHash>();
+        tick(&mut app, 1);
+        let (ready, meshed) = drain(&app);
+        assert!(ready.is_empty());
+        assert_eq!(meshed.len(), 1);
+    }
+
+    #[test]
+    fn remeshing_after_a_settings_change_settles() {
+        let mut app = test_app();
+        run_until_meshed(&mut app, &[IVec3::ZERO], 10).unwrap();
+
+        app.world
+            .resource_mut::<ChunkMeshSettings>()
+            .ambient_occlusion = AmbientOcclusion::Fast;
+        app.update();
+        let dirty = app
+            .world
+            .query_filtered::<(), With<RequiresMeshGeneration>>()
+            .iter(&app.world)
+            .count();
+        assert_eq!(dirty, 1);
+
+        run_until_meshed(&mut app, &[IVec3::ZERO], 10).unwrap();
+    }
+
+    #[test]
+    fn chunks_around_the_origin_get_meshed() {
+        let mut app = test_app();
+        // The world only generates the origin chunk so far, load its neighbors like streaming would
+        app.update();
+        let region: Vec<_> = (-1..=1)
+            .flat_map(|x| (-1..=1).map(move |z| IVec3::new(x, 0, z)))
+            .collect();
+        let terrain_config = app.world.resource::<TerrainConfig>().clone();
+        let world_seed = app.world.resource::<WorldSeed>().0;
+        for &chunk_coords in region.iter().filter(|coords| **coords != IVec3::ZERO) {
+            let chunk = Chunk {
+                chunk_coords,
+                world_seed,
+                ..default()
             }
-            _ => {
-                window.set_cursor_grab_mode(CursorGrabMode::Locked);
-                window.set_cursor_visibility(false);
+            .generate_terrain(&terrain_config);
+            app.world
+                .spawn((chunk, Handle::<Mesh>::default(), RequiresMeshGeneration));
+        }
+
+        run_until_meshed(&mut app, &region, 10).unwrap();
+
+        let mut chunks = app.world.query::<(&Chunk, &Handle<Mesh>)>();
+        let meshes = app.world.resource::<Assets<Mesh>>();
+        let meshed: Vec<_> = chunks
+            .iter(&app.world)
+            .map(|(chunk, mesh)| {
+                let mesh = meshes.get(mesh).unwrap();
+                assert!(
+                    mesh.indices().is_some_and(|indices| !indices.is_empty()),
+                    "chunk {} has an empty mesh",
+                    chunk.chunk_coords
+                );
+                chunk.chunk_coords
+            })
+            .collect();
+        assert_eq!(meshed.len(), region.len());
+        assert!(region.iter().all(|coords| meshed.contains(coords)));
+    }
+
+    #[test]
+    fn chunks_that_never_load_are_reported() {
+        let mut app = test_app();
+        let err = run_until_meshed(&mut app, &[IVec3::ZERO, IVec3::X], 3).unwrap_err();
+        assert!(err.contains(&format!("{:?}", [IVec3::X])), "{}", err);
+    }
+
+    #[test]
+    fn headless_app_generates_and_meshes_the_origin_chunk() {
+        let mut app = test_app();
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let mut chunks = app.world.query::<(&Chunk, &Handle<Mesh>)>();
+        let (chunk, mesh) = chunks.single(&app.world);
+        assert_eq!(chunk.chunk_coords, IVec3::ZERO);
+
+        let world_seed = app.world.resource::<WorldSeed>().0;
+        let surface = app
+            .world
+            .resource::<TerrainConfig>()
+            .surface_height(world_seed, 0, 0);
+        let block_at = |y| chunk.get_block(IVec3::new(0, y, 0));
+        assert_ne!(block_at(surface), Some(&BlockType::Air));
+        assert_eq!(block_at(surface + 1), Some(&BlockType::Air));
+
+        let mesh = app.world.resource::<Assets<Mesh>>().get(mesh).unwrap();
+        assert!(mesh.indices().is_some_and(|indices| !indices.is_empty()));
+    }
+
+    #[test]
+    fn chunk_materials_follow_the_material_settings() {
+        let mut app = test_app();
+        app.insert_resource(ChunkMaterialSettings {
+            base_color: Color::RED,
+            unlit: false,
+        });
+        app.update();
+        let chunk_material = |app: &mut App| {
+            let handle = app
+                .world
+                .query::<&ChunkMaterial>()
+                .single(&app.world)
+                .0
+                .clone();
+            let material = app.world.resource::<Assets<StandardMaterial>>();
+            let material = material.get(&handle).unwrap();
+            (material.base_color, material.unlit)
+        };
+        assert_eq!(chunk_material(&mut app), (Color::RED, false));
+
+        *app.world.resource_mut::<ChunkMaterialSettings>() = ChunkMaterialSettings {
+            base_color: Color::BLUE,
+            unlit: true,
+        };
+        app.update();
+        assert_eq!(chunk_material(&mut app), (Color::BLUE, true));
+    }
+
+    /// Stands in for player physics: falls under gravity until its box would enter a solid block.
+    #[derive(Component, Default)]
+    struct FallingBody {
+        velocity: f32,
+    }
+
+    fn falling_body_system(
+        mut bodies: Query<(&mut Transform, &mut FallingBody)>,
+        chunks: Query<&Chunk>,
+    ) {
+        const STEP: f32 = 1. / 60.;
+        const GRAVITY: f32 = 20.;
+        const HALF_EXTENTS: Vec3 = Vec3::new(0.3, 0.9, 0.3);
+
+        for (mut transform, mut body) in bodies.iter_mut() {
+            body.velocity -= GRAVITY * STEP;
+            // The translation is the body's feet
+            let feet = transform.translation + Vec3::Y * body.velocity * STEP;
+            let center = feet + Vec3::Y * HALF_EXTENTS.y;
+            if aabb::aabb_overlaps_solid(
+                center - HALF_EXTENTS,
+                center + HALF_EXTENTS,
+                chunks.iter(),
+            ) {
+                transform.translation.y = feet.y.floor() + 1.;
+                body.velocity = 0.;
+            } else {
+                transform.translation = feet;
             }
         }
     }
 
-    let window = windows.primary_mut();
+    #[test]
+    fn a_falling_body_lands_on_the_generated_terrain() {
+        let mut app = test_app();
+        app.add_system(falling_body_system);
+        let start = Vec3::new(8.5, 25., 8.5);
+        let body = app
+            .world
+            .spawn((Transform::from_translation(start), FallingBody::default()))
+            .id();
+
+        tick(&mut app, 20);
+        let height = |app: &App| app.world.get::<Transform>(body).unwrap().translation.y;
+        assert!(height(&app) < start.y);
+
+        tick(&mut app, 200);
+        let world_seed = app.world.resource::<WorldSeed>().0;
+        let surface = app
+            .world
+            .resource::<TerrainConfig>()
+            .surface_height(world_seed, 8, 8);
+        assert_eq!(height(&app), surface as f32 + 1.);
+        assert_eq!(app.world.get::<FallingBody>(body).unwrap().velocity, 0.);
+    }
+
+    #[test]
+    fn new_chunks_grow_in_from_their_center() {
+        let duration = Duration::from_millis(200);
+        let mut app = test_app();
+        app.world.resource_mut::<GraphicsSettings>().chunk_fade_in = Some(duration);
+        app.update();
+        let chunk = app
+            .world
+            .query_filtered::<Entity, With<Chunk>>()
+            .single(&app.world);
+        let fade_in = app.world.get::<ChunkFadeIn>(chunk).unwrap();
+        assert!(fade_in.0.percent() < 0.5);
+
+        let mut app = App::new();
+        app.init_resource::<Time>().add_system(chunk_fade_in_system);
+        let fade_in = ChunkFadeIn(Timer::new(duration, TimerMode::Once));
+        assert_eq!(fade_in.0.percent(), 0.);
+        let chunk = Chunk {
+            chunk_coords: IVec3::new(1, 2, -1),
+            ..default()
+        };
+        let origin = chunk.origin().as_vec3();
+        let center = origin + CHUNK_SIZE.as_vec3() / 2.;
+        let chunk = app
+            .world
+            .spawn((fade_in.transform(origin), fade_in, chunk))
+            .id();
+
+        let start = Instant::now();
+        for step in 0..=4 {
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + duration / 8 * step);
+            app.update();
+
+            // The local center of the chunk stays put while it grows
+            let transform = *app.world.get::<Transform>(chunk).unwrap();
+            let drawn_center = transform.transform_point(CHUNK_SIZE.as_vec3() / 2.);
+            assert!(drawn_center.abs_diff_eq(center, 1e-4), "{}", drawn_center);
+            assert!(transform.scale.y < 1.);
+        }
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + duration);
+        app.update();
+        let transform = *app.world.get::<Transform>(chunk).unwrap();
+        assert_eq!(transform, Transform::from_translation(origin));
+        assert!(app.world.get::<ChunkFadeIn>(chunk).is_none());
+    }
+
+    fn cursor_lock_app() -> App {
+        let mut app = test_app();
+        let mut windows = Windows::default();
+        windows.add(Window::new(
+            WindowId::primary(),
+            &WindowDescriptor::default(),
+            800,
+            600,
+            1.,
+            None,
+            None,
+        ));
+        app.insert_resource(windows)
+            .init_resource::<InputSettings>()
+            .add_system(cursor_lock_system);
+        app
+    }
+
+    fn grab_mode(app: &App) -> CursorGrabMode {
+        app.world.resource::<Windows>().primary().cursor_grab_mode()
+    }
+
+    #[test]
+    fn clicking_locks_the_cursor_and_the_lock_key_frees_it() {
+        let mut app = cursor_lock_app();
+
+        press_mouse_button(&mut app, MouseButton::Left);
+        release_mouse_button(&mut app, MouseButton::Left);
+        tick(&mut app, 1);
+        assert_eq!(grab_mode(&app), CursorGrabMode::Locked);
+
+        let lock_key = app.world.resource::<InputSettings>().cursor_lock_key;
+        press_key(&mut app, lock_key);
+        tick(&mut app, 1);
+        release_key(&mut app, lock_key);
+        tick(&mut app, 1);
+        assert_eq!(grab_mode(&app), CursorGrabMode::None);
+    }
+
+    #[test]
+    fn holding_the_lock_key_toggles_the_lock_once() {
+        let mut app = cursor_lock_app();
+        let lock_key = app.world.resource::<InputSettings>().cursor_lock_key;
+
+        press_key(&mut app, lock_key);
+        tick(&mut app, 5);
+        assert_eq!(grab_mode(&app), CursorGrabMode::Locked);
+        release_key(&mut app, lock_key);
+        tick(&mut app, 1);
+        assert_eq!(grab_mode(&app), CursorGrabMode::Locked);
+
+        // Losing focus frees the cursor, and it stays free when the window is focused again
+        app.world
+            .resource_mut::<Windows>()
+            .primary_mut()
+            .update_focused_status_from_backend(false);
+        tick(&mut app, 1);
+        assert_eq!(grab_mode(&app), CursorGrabMode::None);
+        app.world
+            .resource_mut::<Windows>()
+            .primary_mut()
+            .update_focused_status_from_backend(true);
+        tick(&mut app, 1);
+        assert_eq!(grab_mode(&app), CursorGrabMode::None);
+    }
+
+    fn image_of_width(width: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255; 4],
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    #[test]
+    fn packing_textures_too_large_for_an_atlas_fails_without_panicking() {
+        let mut app = test_app();
+        let mut images = app.world.resource_mut::<Assets<Image>>();
+        let small = images.add(image_of_width(4));
+        let huge = images.add(image_of_width(4096));
+
+        assert!(pack_block_textures(&[small.clone(), huge], &mut images).is_err());
+        assert!(pack_block_textures(&[small], &mut images).is_ok());
+    }
+
+    #[test]
+    fn failed_packing_falls_back_to_the_placeholder_textures() {
+        let mut app = test_app();
+        let mut images = app.world.resource_mut::<Assets<Image>>();
+        let placeholder = images.add(image_of_width(4));
+        let huge = images.add(image_of_width(4096));
+        let handles = [placeholder.clone(), huge.clone()];
+
+        let (texture_atlas, is_fallback) = atlas_or_placeholder_fallback(
+            pack_block_textures(&handles, &mut images),
+            std::slice::from_ref(&placeholder),
+            &mut images,
+        );
+        assert!(is_fallback);
+        assert_eq!(texture_atlas.len(), 1);
+        assert!(texture_atlas.get_texture_index(&placeholder).is_some());
+        assert!(texture_atlas.get_texture_index(&huge).is_none());
+
+        let placeholder_uvs = BlockUvs {
+            top: [[0., 0.], [1., 0.], [1., 1.], [0., 1.]],
+            ..default()
+        };
+        let mut uv_mappings = UvMappings::default();
+        insert_block_uvs(
+            &mut uv_mappings,
+            HashMap::from([("Placeholder".to_string(), placeholder_uvs)]),
+            is_fallback,
+        );
+        for block_type in BlockType::ALL {
+            assert_eq!(uv_mappings[&block_type].top, placeholder_uvs.top);
+        }
+    }
+
+    #[test]
+    fn packed_atlases_are_used_as_they_are() {
+        let mut app = test_app();
+        let mut images = app.world.resource_mut::<Assets<Image>>();
+        let handles = [images.add(image_of_width(4)), images.add(image_of_width(8))];
+
+        let (texture_atlas, is_fallback) = atlas_or_placeholder_fallback(
+            pack_block_textures(&handles, &mut images),
+            &handles[..1],
+            &mut images,
+        );
+        assert!(!is_fallback);
+        assert_eq!(texture_atlas.len(), 2);
+    }
+
+    #[test]
+    fn override_textures_replace_the_built_in_ones() {
+        let mut app = test_app();
+        app.insert_resource(TexturePackSettings {
+            override_folder: Some("packs/test".to_string()),
+        })
+        .insert_resource(UvMappings::default());
+
+        // Built-in textures first, the way `load_textures` loads them
+        let paths = [
+            ("textures/blocks/Placeholder/texture.png", 4),
+            ("textures/blocks/Stone/texture.png", 8),
+            ("textures/blocks/Grass/texture.png", 12),
+            ("packs/test/Stone/texture.png", 16),
+        ];
+        let asset_server = app.world.resource::<AssetServer>().clone();
+        let handles: Vec<_> = paths
+            .iter()
+            .map(|(path, width)| {
+                let handle = asset_server.load_untyped(*path);
+                app.world
+                    .resource_mut::<Assets<Image>>()
+                    .set_untracked(handle.id, image_of_width(*width));
+                handle
+            })
+            .collect();
+        app.insert_resource(BlockTextureHandles(handles.clone()));
+
+        SystemStage::single(construct_atlas).run(&mut app.world);
+
+        let atlas = app.world.resource::<BlockAtlasHandle>().clone();
+        let atlases = app.world.resource::<Assets<TextureAtlas>>();
+        let atlas = atlases.get(&atlas).unwrap();
+        let top_left_uv = |handle: &HandleUntyped| {
+            let index = atlas.get_texture_index(&handle.typed_weak()).unwrap();
+            (atlas.textures[index].min / atlas.size).to_array()
+        };
+
+        let uv_mappings = app.world.resource::<UvMappings>();
+        assert_eq!(
+            uv_mappings[&BlockType::Stone].top[0],
+            top_left_uv(&handles[3])
+        );
+        assert_ne!(
+            uv_mappings[&BlockType::Stone].top[0],
+            top_left_uv(&handles[1])
+        );
+        assert_eq!(
+            uv_mappings[&BlockType::Grass].top[0],
+            top_left_uv(&handles[2])
+        );
+    }
+
+    #[test]
+    fn face_culling_rules() {
+        use BlockType::*;
+
+        // Solid against solid
+        assert!(!should_render_face(&Stone, &Grass));
+        assert!(!should_render_face(&Bedrock, &Placeholder));
+        // Solid against air
+        assert!(should_render_face(&Stone, &Air));
+        assert!(should_render_face(&Grass, &Air));
+        // Air has no faces of its own
+        assert!(!should_render_face(&Air, &Stone));
+        assert!(!should_render_face(&Air, &Air));
+    }
+
+    /// Only `mesh_generation_system`, remeshing a single chunk per frame.
+    fn single_mesh_per_frame_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .insert_resource(test_uv_mappings())
+            .insert_resource(ChunkMeshSettings {
+                max_meshes_per_frame: 1,
+                look_direction_weight: 0.,
+                ..default()
+            })
+            .add_event::<ChunkMeshed>()
+            .add_system(mesh_generation_system);
+        app
+    }
+
+    #[test]
+    fn camera_chunk_is_remeshed_first_and_the_target_is_aimed_through_clean_chunks() {
+        let mut app = single_mesh_per_frame_app();
+
+        let mut spawn_chunk = |chunk_coords: IVec3, solid: bool, dirty: bool| {
+            let mut chunk = Chunk {
+                chunk_coords,
+                ..default()
+            };
+            if solid {
+                for (_, block) in chunk.iter_blocks_mut() {
+                    *block = BlockType::Stone;
+                }
+            }
+            let mut entity = app.world.spawn((
+                chunk,
+                Handle::<Mesh>::default(),
+                ChunkMemoryUsage::default(),
+            ));
+            if dirty {
+                entity.insert(RequiresMeshGeneration);
+            }
+            entity.id()
+        };
+        // The camera looks along +X at a clean chunk hiding a dirty one, while another dirty chunk
+        // is nearer beside it
+        let camera_chunk = spawn_chunk(IVec3::ZERO, false, true);
+        spawn_chunk(IVec3::X, true, false);
+        let hidden = spawn_chunk(IVec3::X * 2, true, true);
+        let beside = spawn_chunk(IVec3::Z, true, true);
+
+        let eye = Vec3::new(8., 16., 8.);
+        app.world.spawn((
+            MainCamera,
+            GlobalTransform::from(
+                Transform::from_translation(eye).looking_at(eye + Vec3::X, Vec3::Y),
+            ),
+        ));
+
+        let is_dirty =
+            |app: &App, entity| app.world.get::<RequiresMeshGeneration>(entity).is_some();
+        app.update();
+        assert!(!is_dirty(&app, camera_chunk));
+        assert!(is_dirty(&app, hidden) && is_dirty(&app, beside));
+
+        app.update();
+        assert!(!is_dirty(&app, beside));
+        assert!(is_dirty(&app, hidden));
+    }
+
+    #[test]
+    fn chunks_ahead_are_remeshed_before_equally_near_ones_beside() {
+        let mut app = single_mesh_per_frame_app();
+        app.world
+            .resource_mut::<ChunkMeshSettings>()
+            .look_direction_weight = 0.5;
+        let mut spawn_dirty_chunk = |chunk_coords: IVec3| {
+            app.world
+                .spawn((
+                    Chunk {
+                        chunk_coords,
+                        ..default()
+                    },
+                    Handle::<Mesh>::default(),
+                    RequiresMeshGeneration,
+                ))
+                .id()
+        };
+        // Spawned first so it would win a tie
+        let beside = spawn_dirty_chunk(IVec3::X);
+        let ahead = spawn_dirty_chunk(IVec3::Z);
+
+        // Facing +Z from the middle of the empty chunk between them
+        let eye = Vec3::new(8., 16., 8.);
+        app.world.spawn((
+            MainCamera,
+            GlobalTransform::from(
+                Transform::from_translation(eye).looking_at(eye + Vec3::Z, Vec3::Y),
+            ),
+        ));
+
+        app.update();
+        assert!(app.world.get::<RequiresMeshGeneration>(ahead).is_none());
+        assert!(app.world.get::<RequiresMeshGeneration>(beside).is_some());
+    }
+
+    #[test]
+    fn crossing_a_chunk_boundary_sends_one_camera_chunk_event() {
+        let mut app = App::new();
+        app.add_event::<CameraChunkChanged>()
+            .add_system(camera_chunk_changed_system);
+        let camera = app
+            .world
+            .spawn((MainCamera, GlobalTransform::from_xyz(2., 16., 8.)))
+            .id();
+
+        let mut reader = ManualEventReader::<CameraChunkChanged>::default();
+        let mut sent = Vec::new();
+        // Walking along +X, across the boundary at x 16 halfway through
+        for x in [2., 6., 10., 14., 18., 22., 26.] {
+            *app.world.get_mut::<GlobalTransform>(camera).unwrap() =
+                GlobalTransform::from_xyz(x, 16., 8.);
+            app.update();
+            let events = app.world.resource::<Events<CameraChunkChanged>>();
+            sent.extend(reader.iter(events).map(|event| (event.from, event.to)));
+        }
+
+        assert_eq!(sent, [(IVec3::ZERO, IVec3::X)]);
+    }
+
+    #[test]
+    fn unchanged_chunks_skip_meshing_without_using_the_budget() {
+        let mut app = single_mesh_per_frame_app();
+        let chunk = chunk_with(&[(IVec3::ONE, BlockType::Stone)]);
+        let content_hash = chunk.content_hash();
+        let mut spawn_chunk = |chunk_coords: IVec3, meshed_hash: u64| {
+            app.world
+                .spawn((
+                    Chunk {
+                        chunk_coords,
+                        blocks: chunk.blocks,
+                        ..default()
+                    },
+                    Handle::<Mesh>::default(),
+                    ChunkMemoryUsage::default(),
+                    MeshedContentHash(meshed_hash),
+                    RequiresMeshGeneration,
+                ))
+                .id()
+        };
+        let unchanged = spawn_chunk(IVec3::ZERO, content_hash);
+        let edited = spawn_chunk(IVec3::X, content_hash ^ 1);
+
+        app.update();
+
+        for entity in [unchanged, edited] {
+            assert!(app.world.get::<RequiresMeshGeneration>(entity).is_none());
+        }
+        let mesh = |entity| app.world.get::<Handle<Mesh>>(entity).unwrap().clone();
+        assert_eq!(mesh(unchanged), Handle::default());
+        assert_ne!(mesh(edited), Handle::default());
+    }
+
+    #[test]
+    fn meshing_refreshes_the_memory_usage_and_does_not_need_it() {
+        let mut app = single_mesh_per_frame_app();
+        app.world
+            .resource_mut::<ChunkMeshSettings>()
+            .max_meshes_per_frame = 2;
+        let chunk = chunk_with(&[(IVec3::ONE, BlockType::Stone)]);
+        let with_usage = app
+            .world
+            .spawn((
+                Chunk {
+                    blocks: chunk.blocks,
+                    ..default()
+                },
+                Handle::<Mesh>::default(),
+                ChunkMemoryUsage::default(),
+                RequiresMeshGeneration,
+            ))
+            .id();
+        let without_usage = app
+            .world
+            .spawn((
+                Chunk {
+                    chunk_coords: IVec3::X,
+                    blocks: chunk.blocks,
+                    ..default()
+                },
+                Handle::<Mesh>::default(),
+                RequiresMeshGeneration,
+            ))
+            .id();
+
+        app.update();
+
+        // One byte per block, and a lone block's 6 faces of 4 vertices with a position, normal,
+        // uv and color (12 + 12 + 8 + 16 bytes) and 6 u32 indices each
+        let usage = *app.world.get::<ChunkMemoryUsage>(with_usage).unwrap();
+        assert_eq!(usage.blocks, 16 * 32 * 16);
+        assert_eq!(usage.mesh, 6 * (4 * 48 + 6 * 4));
+
+        assert!(app
+            .world
+            .get::<RequiresMeshGeneration>(without_usage)
+            .is_none());
+        assert_ne!(
+            *app.world.get::<Handle<Mesh>>(without_usage).unwrap(),
+            Handle::default()
+        );
+    }
 
-    if keys.pressed(KeyCode::Return) {
-        toggle_mouse_lock(window);
+    #[test]
+    fn meshing_records_the_triangle_count() {
+        let mut app = single_mesh_per_frame_app();
+        let entity = app
+            .world
+            .spawn((
+                chunk_with(&[(IVec3::ONE, BlockType::Stone)]),
+                Handle::<Mesh>::default(),
+                RequiresMeshGeneration,
+            ))
+            .id();
+        app.update();
+
+        let mesh = app.world.get::<Handle<Mesh>>(entity).unwrap();
+        let indices = app
+            .world
+            .resource::<Assets<Mesh>>()
+            .get(mesh)
+            .and_then(Mesh::indices)
+            .unwrap()
+            .len();
+        let triangle_count = *app.world.get::<ChunkTriangleCount>(entity).unwrap();
+        // Two for each of the lone block's faces
+        assert_eq!(triangle_count, ChunkTriangleCount(6 * 2));
+        assert_eq!(triangle_count.0, indices / 3);
     }
 
-    if !window.is_focused() && !window.cursor_visible() {
-        toggle_mouse_lock(window);
+    #[test]
+    fn editing_blocks_refreshes_the_memory_usage() {
+        let mut app = single_mesh_per_frame_app();
+        let entity = app
+            .world
+            .spawn((
+                chunk_with(&[(IVec3::ONE, BlockType::Stone)]),
+                Handle::<Mesh>::default(),
+                ChunkMemoryUsage::default(),
+                RequiresMeshGeneration,
+            ))
+            .id();
+        app.update();
+        let mesh_bytes = |app: &App| app.world.get::<ChunkMemoryUsage>(entity).unwrap().mesh;
+        let one_block = mesh_bytes(&app);
+
+        // A second block apart from the first doubles the faces
+        let mut chunk = app.world.entity_mut(entity);
+        *chunk
+            .get_mut::<Chunk>()
+            .unwrap()
+            .get_block_mut(IVec3::splat(4))
+            .unwrap() = BlockType::Stone;
+        chunk.insert(RequiresMeshGeneration);
+        app.update();
+
+        assert_eq!(mesh_bytes(&app), 2 * one_block);
     }
 }
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{rng::WorldRng, BlockType, Chunk, CHUNK_SIZE};
+
+/// Picks random blocks in every loaded chunk at a steady rate and sends a [`RandomTickEvent`]
+/// for each, so slow world changes like grass spreading or crops growing share one scheduler.
+pub struct RandomTickPlugin;
+
+impl Plugin for RandomTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RandomTick>()
+            .add_event::<RandomTickEvent>()
+            .add_system(random_tick_system);
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct RandomTick {
+    /// How many blocks are picked in each chunk every `interval`.
+    pub ticks_per_chunk_per_interval: u32,
+    /// Never shorter than [`MIN_RANDOM_TICK_INTERVAL`].
+    pub interval: Duration,
+}
+
+pub const MIN_RANDOM_TICK_INTERVAL: Duration = Duration::from_millis(1);
+
+impl Default for RandomTick {
+    fn default() -> Self {
+        Self {
+            ticks_per_chunk_per_interval: 3,
+            interval: Duration::from_millis(50),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RandomTickEvent {
+    pub world_pos: IVec3,
+    pub block: BlockType,
+}
+
+fn random_tick_system(
+    time: Res<Time>,
+    random_tick: Res<RandomTick>,
+    mut rng: ResMut<WorldRng>,
+    mut timer: Local<Timer>,
+    chunks: Query<&Chunk>,
+    mut random_tick_events: EventWriter<RandomTickEvent>,
+) {
+    if random_tick.is_changed() {
+        // A zero duration timer can't repeat
+        let interval = random_tick.interval.max(MIN_RANDOM_TICK_INTERVAL);
+        *timer = Timer::new(interval, TimerMode::Repeating);
+    }
+
+    timer.tick(time.delta());
+    for _ in 0..timer.times_finished_this_tick() {
+        for chunk in chunks.iter() {
+            for _ in 0..random_tick.ticks_per_chunk_per_interval {
+                let pos = IVec3::new(
+                    rng.gen_range(0..CHUNK_SIZE.x),
+                    rng.gen_range(0..CHUNK_SIZE.y),
+                    rng.gen_range(0..CHUNK_SIZE.z),
+                );
+
+                random_tick_events.send(RandomTickEvent {
+                    world_pos: chunk.origin() + pos,
+                    block: *chunk.get_block(pos).unwrap(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use bevy::ecs::event::Events;
+
+    use super::*;
+    use crate::rng::WorldSeed;
+
+    /// Runs two intervals of random ticks over two chunks, returning the ticked positions.
+    fn tick_positions(random_tick: RandomTick) -> Vec<IVec3> {
+        let mut app = App::new();
+        app.insert_resource(random_tick.clone())
+            .insert_resource(WorldRng::from_seed(WorldSeed(42)))
+            .init_resource::<Time>()
+            .add_event::<RandomTickEvent>()
+            .add_system(random_tick_system);
+        for chunk_coords in [IVec3::ZERO, IVec3::X] {
+            app.world.spawn(Chunk {
+                chunk_coords,
+                ..default()
+            });
+        }
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        app.update();
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + random_tick.interval * 2);
+        app.update();
+
+        let events = app.world.resource::<Events<RandomTickEvent>>();
+        events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.world_pos)
+            .collect()
+    }
+
+    #[test]
+    fn fires_the_configured_ticks_per_chunk_per_interval() {
+        let random_tick = RandomTick {
+            ticks_per_chunk_per_interval: 3,
+            interval: Duration::from_millis(50),
+        };
+        let positions = tick_positions(random_tick);
+
+        assert_eq!(positions.len(), 2 * 2 * 3);
+        for chunk_coords in [IVec3::ZERO, IVec3::X] {
+            let in_chunk = positions
+                .iter()
+                .filter(|pos| Chunk::world_coord_chunk(pos.as_vec3()) == chunk_coords)
+                .count();
+            assert_eq!(in_chunk, 2 * 3);
+        }
+    }
+
+    #[test]
+    fn same_seed_ticks_the_same_blocks() {
+        let random_tick = RandomTick::default();
+        assert_eq!(
+            tick_positions(random_tick.clone()),
+            tick_positions(random_tick)
+        );
+    }
+
+    #[test]
+    fn zero_interval_ticks_at_the_minimum_interval() {
+        let mut app = App::new();
+        app.insert_resource(RandomTick {
+            ticks_per_chunk_per_interval: 1,
+            interval: Duration::ZERO,
+        })
+        .insert_resource(WorldRng::from_seed(WorldSeed(42)))
+        .init_resource::<Time>()
+        .add_event::<RandomTickEvent>()
+        .add_system(random_tick_system);
+        app.world.spawn(Chunk::default());
+
+        let start = Instant::now();
+        let mut ticks = 0;
+        for update in 0..=5 {
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + MIN_RANDOM_TICK_INTERVAL * 2 * update);
+            app.update();
+            ticks += app
+                .world
+                .resource_mut::<Events<RandomTickEvent>>()
+                .drain()
+                .count();
+        }
+
+        // Two minimum intervals pass in each update after the first
+        assert_eq!(ticks, 5 * 2);
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+
+use crate::Chunk;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelHit {
+    /// The voxel that was hit.
+    pub pos: IVec3,
+    /// Normal of the face the ray entered through, zero if the ray started inside the voxel.
+    pub normal: IVec3,
+    /// Distance along the ray to where it entered the voxel.
+    pub distance: f32,
+}
+
+/// Upper bound on the voxels a single raycast visits, so even an unbounded `max_distance` can't
+/// make it loop forever.
+pub const MAX_RAY_STEPS: usize = 1024;
+
+/// Walks the voxels along a ray (Amanatides & Woo DDA) and returns the first non-transparent
+/// block within `max_distance`. Positions are in the chunk's local block coordinates.
+pub fn raycast(
+    chunk: &Chunk,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> Option<VoxelHit> {
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO || !origin.is_finite() {
+        return None;
+    }
+
+    // Every step crosses into the next voxel along one axis, so a ray can't visit more than this
+    // many voxels before it has travelled `max_distance`
+    let max_steps = (3. * (max_distance.max(0.) + 1.))
+        .ceil()
+        .min(MAX_RAY_STEPS as f32) as usize;
+
+    let mut voxel = origin.floor().as_ivec3();
+    let mut step = IVec3::ZERO;
+    let mut t_max = Vec3::splat(f32::INFINITY);
+    let mut t_delta = Vec3::splat(f32::INFINITY);
+
+    for axis in 0..3 {
+        if direction[axis] > 0. {
+            step[axis] = 1;
+            t_max[axis] = (voxel[axis] as f32 + 1. - origin[axis]) / direction[axis];
+            t_delta[axis] = 1. / direction[axis];
+        } else if direction[axis] < 0. {
+            step[axis] = -1;
+            t_max[axis] = (voxel[axis] as f32 - origin[axis]) / direction[axis];
+            t_delta[axis] = -1. / direction[axis];
+        }
+    }
+
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.;
+
+    for _ in 0..=max_steps {
+        if distance > max_distance {
+            break;
+        }
+
+        if matches!(chunk.get_block(voxel), Some(block) if !block.is_transparent()) {
+            return Some(VoxelHit {
+                pos: voxel,
+                normal,
+                distance,
+            });
+        }
+
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+
+        distance = t_max[axis];
+        voxel[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+
+    None
+}
+
+/// Raycasts from a world space `origin` through all `chunks` and returns the nearest hit along with
+/// the chunk it is in. The hit position is in that chunk's local block coordinates.
+pub fn raycast_chunks<'a>(
+    chunks: impl IntoIterator<Item = &'a Chunk>,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> Option<(&'a Chunk, VoxelHit)> {
+    chunks
+        .into_iter()
+        .filter_map(|chunk| {
+            let local_origin = origin - chunk.origin().as_vec3();
+            raycast(chunk, local_origin, direction, max_distance).map(|hit| (chunk, hit))
+        })
+        .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockType, CHUNK_SIZE};
+
+    fn ground_chunk() -> Chunk {
+        let mut chunk = Chunk::default();
+        for (pos, block) in chunk.iter_blocks_mut() {
+            if pos.y < 4 {
+                *block = BlockType::Stone;
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn downward_ray_hits_the_ground_face() {
+        let hit = raycast(&ground_chunk(), Vec3::new(2.5, 10.5, 2.5), -Vec3::Y, 20.).unwrap();
+
+        assert_eq!(hit.pos, IVec3::new(2, 3, 2));
+        assert_eq!(hit.normal, IVec3::Y);
+        assert!((hit.distance - 6.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_stops_at_max_distance() {
+        assert_eq!(
+            raycast(&ground_chunk(), Vec3::new(2.5, 10.5, 2.5), -Vec3::Y, 6.),
+            None
+        );
+    }
+
+    #[test]
+    fn sky_facing_ray_misses_within_the_step_limit() {
+        let chunk = ground_chunk();
+        let origin = Vec3::new(2.5, 10.5, 2.5);
+
+        assert_eq!(raycast(&chunk, origin, Vec3::Y, 1e6), None);
+        assert_eq!(
+            raycast(&chunk, origin, Vec3::new(1., 1., 1.), f32::INFINITY),
+            None
+        );
+        // Past the top of the chunk, even when aimed back down from too far away
+        let above = Vec3::Y * (MAX_RAY_STEPS as f32 + CHUNK_SIZE.y as f32);
+        assert_eq!(raycast(&chunk, above, -Vec3::Y, f32::INFINITY), None);
+    }
+
+    #[test]
+    fn zero_direction_ray_misses() {
+        assert_eq!(
+            raycast(&ground_chunk(), Vec3::new(2.5, 1.5, 2.5), Vec3::ZERO, 20.),
+            None
+        );
+    }
+}
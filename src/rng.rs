@@ -0,0 +1,137 @@
+//! Randomness for the world comes in two kinds:
+//!
+//! - Position-deterministic: anything that shapes generated terrain, like structures and ores,
+//!   must only depend on the world seed and a position, so a chunk comes out the same however
+//!   often it is regenerated and in whatever order chunks are loaded. Hash positions with
+//!   [`position_hash`], seeded per layer by [`seed_for`].
+//! - Stateful: anything that only happens while the game runs, like random ticks or particles,
+//!   draws from the shared [`WorldRng`], which is seeded from the [`WorldSeed`] so a run can be
+//!   reproduced.
+
+use std::fmt;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct WorldSeed(pub u64);
+
+/// Formats the seed the way it is shown to players, so it can be shared as is.
+impl fmt::Display for WorldSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Resource, Deref, DerefMut)]
+pub struct WorldRng(pub StdRng);
+
+impl WorldRng {
+    pub fn from_seed(seed: WorldSeed) -> Self {
+        Self(StdRng::seed_from_u64(splitmix64(seed.0)))
+    }
+}
+
+impl FromWorld for WorldRng {
+    fn from_world(world: &mut World) -> Self {
+        let seed = world
+            .get_resource::<WorldSeed>()
+            .copied()
+            .unwrap_or_default();
+        Self::from_seed(seed)
+    }
+}
+
+/// A generation layer with its own random source, so layers don't correlate with each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// Hills and valleys of the surface.
+    Height,
+    /// Offsets where the height is sampled, see `TerrainConfig::warp_strength`.
+    Warp,
+    Boulders,
+}
+
+impl Feature {
+    fn salt(self) -> u64 {
+        match self {
+            Feature::Height => 0x4e16_4785,
+            Feature::Warp => 0x3d9c_e21f,
+            Feature::Boulders => 0x51a7_b0b5,
+        }
+    }
+}
+
+/// Seed for one generation layer, derived from the world seed.
+pub fn seed_for(world_seed: u64, feature: Feature) -> u64 {
+    splitmix64(world_seed ^ feature.salt())
+}
+
+/// Hashes a position together with a seed, so the same inputs always give the same value.
+pub fn position_hash(seed: u64, pos: IVec3) -> u64 {
+    [pos.x, pos.y, pos.z]
+        .into_iter()
+        .fold(splitmix64(seed), |hash, coord| {
+            splitmix64(hash ^ coord as u32 as u64)
+        })
+}
+
+/// Maps a hash to `[0, 1)`.
+pub fn unit_f32(hash: u64) -> f32 {
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_the_same_sequence() {
+        let draw = |seed| {
+            let mut rng = WorldRng::from_seed(WorldSeed(seed));
+            (0..16).map(|_| rng.gen::<u64>()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(draw(1234), draw(1234));
+        assert_ne!(draw(1234), draw(1235));
+    }
+
+    /// Every generation layer; extend along with [`Feature`].
+    const FEATURES: &[Feature] = &[Feature::Height, Feature::Warp, Feature::Boulders];
+
+    #[test]
+    fn feature_seeds_are_deterministic_and_distinct() {
+        let world_seed = 99;
+        let seeds: Vec<_> = FEATURES
+            .iter()
+            .map(|&feature| seed_for(world_seed, feature))
+            .collect();
+
+        for (i, &feature) in FEATURES.iter().enumerate() {
+            assert_eq!(seed_for(world_seed, feature), seeds[i]);
+            assert_ne!(seeds[i], world_seed);
+            assert_ne!(seed_for(world_seed + 1, feature), seeds[i]);
+            assert!(
+                !seeds[..i].contains(&seeds[i]),
+                "{:?} shares a seed",
+                feature
+            );
+        }
+    }
+
+    #[test]
+    fn seeds_are_shown_as_plain_numbers() {
+        assert_eq!(WorldSeed(0).to_string(), "0");
+        assert_eq!(WorldSeed(1234).to_string(), "1234");
+        assert_eq!(WorldSeed(u64::MAX).to_string(), "18446744073709551615");
+    }
+}
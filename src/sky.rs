@@ -0,0 +1,274 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{mesh::VertexAttributeValues, render_resource::Face},
+};
+
+use crate::MainCamera;
+
+/// Draws the sky as a gradient dome around the camera, colored by the [`TimeOfDay`], and dims the
+/// ambient light at night.
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .init_resource::<AmbientSettings>()
+            .add_startup_system(setup_sky)
+            .add_system(follow_camera_system)
+            .add_system(update_sky_colors_system)
+            .add_system(update_ambient_light_system);
+    }
+}
+
+/// Fraction of the day that has passed: 0 is midnight, 0.25 sunrise, 0.5 noon and 0.75 sunset.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct TimeOfDay(pub f32);
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+/// Minimum light reaching every surface, so faces facing away from the sun aren't pitch black.
+/// Only visible on lit materials.
+#[derive(Resource, Clone, Debug)]
+pub struct AmbientSettings {
+    pub color: Color,
+    /// Brightness at noon.
+    pub day_brightness: f32,
+    /// Brightness at midnight.
+    pub night_brightness: f32,
+}
+
+impl Default for AmbientSettings {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            day_brightness: 0.3,
+            night_brightness: 0.05,
+        }
+    }
+}
+
+impl AmbientSettings {
+    pub fn brightness(&self, time_of_day: TimeOfDay) -> f32 {
+        // 0 at midnight, 1 at noon
+        let daylight = 0.5 - 0.5 * (time_of_day.0 * std::f32::consts::TAU).cos();
+        self.night_brightness + (self.day_brightness - self.night_brightness) * daylight
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkyColors {
+    pub horizon: Color,
+    pub zenith: Color,
+}
+
+const NIGHT: SkyColors = SkyColors {
+    horizon: Color::rgb(0.05, 0.05, 0.12),
+    zenith: Color::rgb(0.01, 0.01, 0.05),
+};
+
+const TWILIGHT: SkyColors = SkyColors {
+    horizon: Color::rgb(0.95, 0.55, 0.3),
+    zenith: Color::rgb(0.3, 0.35, 0.6),
+};
+
+const DAY: SkyColors = SkyColors {
+    horizon: Color::rgb(0.6, 0.75, 0.95),
+    zenith: Color::rgb(0.2, 0.35, 0.85),
+};
+
+/// Sky colors over the day, blended linearly in between.
+const SKY_KEYFRAMES: [(f32, SkyColors); 8] = [
+    (0., NIGHT),
+    (0.2, NIGHT),
+    (0.25, TWILIGHT),
+    (0.32, DAY),
+    (0.68, DAY),
+    (0.75, TWILIGHT),
+    (0.8, NIGHT),
+    (1., NIGHT),
+];
+
+impl SkyColors {
+    pub fn at(time_of_day: TimeOfDay) -> SkyColors {
+        let time = time_of_day.0.rem_euclid(1.);
+        let next = SKY_KEYFRAMES
+            .iter()
+            .position(|(keyframe_time, _)| *keyframe_time >= time)
+            .unwrap_or(SKY_KEYFRAMES.len() - 1)
+            .max(1);
+
+        let (start_time, start) = SKY_KEYFRAMES[next - 1];
+        let (end_time, end) = SKY_KEYFRAMES[next];
+        let t = (time - start_time) / (end_time - start_time);
+
+        SkyColors {
+            horizon: lerp_color(start.horizon, end.horizon, t),
+            zenith: lerp_color(start.zenith, end.zenith, t),
+        }
+    }
+
+    /// Color of the sky in the given direction from the camera.
+    fn sample(&self, direction: Vec3) -> Color {
+        let height = direction.normalize_or_zero().y.max(0.);
+        lerp_color(self.horizon, self.zenith, height)
+    }
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    Vec4::from(a).lerp(Vec4::from(b), t).into()
+}
+
+/// Large enough to surround the world, small enough to stay within the camera's far plane.
+const SKY_RADIUS: f32 = 500.;
+
+#[derive(Component)]
+struct SkyDome;
+
+fn setup_sky(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time_of_day: Res<TimeOfDay>,
+) {
+    let mut mesh = Mesh::from(shape::UVSphere {
+        radius: SKY_RADIUS,
+        sectors: 32,
+        stacks: 16,
+    });
+    set_sky_colors(&mut mesh, SkyColors::at(*time_of_day));
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                unlit: true,
+                // The dome is seen from the inside
+                cull_mode: Some(Face::Front),
+                ..default()
+            }),
+            ..default()
+        },
+        NotShadowCaster,
+        NotShadowReceiver,
+        SkyDome,
+    ));
+}
+
+fn set_sky_colors(mesh: &mut Mesh, sky_colors: SkyColors) {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return;
+    };
+
+    let colors: Vec<[f32; 4]> = positions
+        .iter()
+        .map(|position| {
+            sky_colors
+                .sample(Vec3::from(*position))
+                .as_linear_rgba_f32()
+        })
+        .collect();
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+}
+
+fn follow_camera_system(
+    cameras: Query<&Transform, (With<MainCamera>, Without<SkyDome>)>,
+    mut domes: Query<&mut Transform, With<SkyDome>>,
+) {
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+
+    for mut dome in domes.iter_mut() {
+        dome.translation = camera.translation;
+    }
+}
+
+fn update_sky_colors_system(
+    time_of_day: Res<TimeOfDay>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    domes: Query<&Handle<Mesh>, With<SkyDome>>,
+) {
+    if !time_of_day.is_changed() {
+        return;
+    }
+
+    let sky_colors = SkyColors::at(*time_of_day);
+    for handle in domes.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            set_sky_colors(mesh, sky_colors);
+        }
+    }
+}
+
+fn update_ambient_light_system(
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<AmbientSettings>,
+    mut ambient_light: ResMut<AmbientLight>,
+) {
+    if !time_of_day.is_changed() && !settings.is_changed() {
+        return;
+    }
+
+    ambient_light.color = settings.color;
+    ambient_light.brightness = settings.brightness(*time_of_day);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sunrise_horizon_is_warm() {
+        let horizon = SkyColors::at(TimeOfDay(0.25)).horizon;
+        assert!(horizon.r() > horizon.b(), "{:?}", horizon);
+    }
+
+    #[test]
+    fn noon_sky_is_blue() {
+        let sky = SkyColors::at(TimeOfDay(0.5));
+        for color in [sky.horizon, sky.zenith] {
+            assert!(
+                color.b() > color.r() && color.b() > color.g(),
+                "{:?}",
+                color
+            );
+        }
+    }
+
+    #[test]
+    fn ambient_settings_set_the_ambient_light() {
+        let mut app = App::new();
+        app.init_resource::<AmbientLight>()
+            .insert_resource(TimeOfDay(0.5))
+            .insert_resource(AmbientSettings {
+                color: Color::ORANGE,
+                day_brightness: 0.6,
+                night_brightness: 0.1,
+            })
+            .add_system(update_ambient_light_system);
+
+        app.update();
+        let ambient_light = app.world.resource::<AmbientLight>();
+        assert_eq!(ambient_light.color, Color::ORANGE);
+        assert!((ambient_light.brightness - 0.6).abs() < 1e-6);
+
+        *app.world.resource_mut::<TimeOfDay>() = TimeOfDay(0.);
+        app.update();
+        let brightness = app.world.resource::<AmbientLight>().brightness;
+        assert!((brightness - 0.1).abs() < 1e-6);
+
+        app.world.resource_mut::<AmbientSettings>().night_brightness = 0.2;
+        app.update();
+        let brightness = app.world.resource::<AmbientLight>().brightness;
+        assert!((brightness - 0.2).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+
+use crate::{
+    rng::{position_hash, seed_for, unit_f32, Feature},
+    terrain::TerrainConfig,
+    BlockType,
+};
+
+/// Something built out of blocks around an origin, like a tree or an ore vein.
+pub trait Structure {
+    /// Writes the structure's blocks, in world coordinates, for a structure placed at `origin`.
+    fn place(&self, origin: IVec3, write: &mut dyn FnMut(IVec3, BlockType));
+}
+
+/// Scatters a [`Structure`] over the world's surface. Every chunk derives the same origins from
+/// the world seed, so a structure whose origin is in a neighbouring chunk still writes the blocks
+/// that reach into this one and structures cross chunk borders seamlessly.
+pub struct StructurePlacer<S> {
+    pub structure: S,
+    /// Gives the placer its own random source, so placers using the same grid don't pick the
+    /// same origins.
+    pub feature: Feature,
+    /// At most one structure is placed in every `cell_size` × `cell_size` column cell.
+    pub cell_size: i32,
+    /// Chance of a cell containing a structure.
+    pub density: f32,
+    /// How far, horizontally, a structure's blocks may reach from its origin.
+    pub max_radius: i32,
+}
+
+impl<S: Structure> StructurePlacer<S> {
+    /// Origins of all structures that may write blocks into the `size` blocks from
+    /// `region_origin`, like a chunk.
+    pub fn origins_near(
+        &self,
+        world_seed: u64,
+        region_origin: IVec3,
+        size: IVec3,
+        config: &TerrainConfig,
+    ) -> Vec<IVec3> {
+        let min = region_origin - self.max_radius;
+        let max = region_origin + size - 1 + self.max_radius;
+
+        let seed = seed_for(world_seed, self.feature);
+        let mut origins = Vec::new();
+        for cell_x in min.x.div_euclid(self.cell_size)..=max.x.div_euclid(self.cell_size) {
+            for cell_z in min.z.div_euclid(self.cell_size)..=max.z.div_euclid(self.cell_size) {
+                let hash = position_hash(seed, IVec3::new(cell_x, 0, cell_z));
+                if unit_f32(hash) >= self.density {
+                    continue;
+                }
+
+                let cell_size = self.cell_size as u64;
+                let x = cell_x * self.cell_size + ((hash >> 8) % cell_size) as i32;
+                let z = cell_z * self.cell_size + ((hash >> 16) % cell_size) as i32;
+                origins.push(IVec3::new(
+                    x,
+                    config.surface_height(world_seed, x, z) + 1,
+                    z,
+                ));
+            }
+        }
+
+        origins
+    }
+
+    /// Writes the blocks, in world coordinates, that structures place within the `size` blocks
+    /// from `region_origin`.
+    pub fn place_in_region(
+        &self,
+        world_seed: u64,
+        region_origin: IVec3,
+        size: IVec3,
+        config: &TerrainConfig,
+        write: &mut dyn FnMut(IVec3, BlockType),
+    ) {
+        let max = region_origin + size;
+        for origin in self.origins_near(world_seed, region_origin, size, config) {
+            self.structure.place(origin, &mut |world_pos, block| {
+                if world_pos.cmpge(region_origin).all() && world_pos.cmplt(max).all() {
+                    write(world_pos, block);
+                }
+            });
+        }
+    }
+}
+
+/// A small lump of stone lying on the surface.
+pub struct Boulder;
+
+impl Structure for Boulder {
+    fn place(&self, origin: IVec3, write: &mut dyn FnMut(IVec3, BlockType)) {
+        for x in -1..=1 {
+            for z in -1..=1 {
+                write(origin + IVec3::new(x, 0, z), BlockType::Stone);
+            }
+        }
+        write(origin + IVec3::Y, BlockType::Stone);
+    }
+}
+
+pub const BOULDERS: StructurePlacer<Boulder> = StructurePlacer {
+    structure: Boulder,
+    feature: Feature::Boulders,
+    cell_size: 8,
+    density: 0.1,
+    max_radius: 1,
+};
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{Chunk, CHUNK_SIZE};
+
+    /// A row of stone along x, wide enough to reach into the neighbouring chunk.
+    struct Wall;
+
+    impl Structure for Wall {
+        fn place(&self, origin: IVec3, write: &mut dyn FnMut(IVec3, BlockType)) {
+            for x in -3..=3 {
+                write(origin + IVec3::new(x, 0, 0), BlockType::Stone);
+            }
+        }
+    }
+
+    const WALLS: StructurePlacer<Wall> = StructurePlacer {
+        structure: Wall,
+        feature: Feature::Boulders,
+        cell_size: 4,
+        density: 1.,
+        max_radius: 3,
+    };
+
+    #[test]
+    fn structures_crossing_a_chunk_border_match_on_both_sides() {
+        let config = TerrainConfig::default();
+        let world_seed = 7;
+        let chunks: Vec<Chunk> = [IVec3::ZERO, IVec3::X]
+            .into_iter()
+            .map(|chunk_coords| {
+                let mut chunk = Chunk {
+                    chunk_coords,
+                    world_seed,
+                    ..default()
+                };
+                let origin = chunk.origin();
+                WALLS.place_in_region(
+                    world_seed,
+                    origin,
+                    CHUNK_SIZE,
+                    &config,
+                    &mut |world_pos, block| {
+                        *chunk.get_block_mut(world_pos - origin).unwrap() = block;
+                    },
+                );
+                chunk
+            })
+            .collect();
+
+        // Every block of every structure, placed without chunks
+        let mut expected = HashMap::new();
+        let mut crossing = 0;
+        for chunk in &chunks {
+            for origin in WALLS.origins_near(world_seed, chunk.origin(), CHUNK_SIZE, &config) {
+                let owner = Chunk::world_coord_chunk(origin.as_vec3());
+                WALLS.structure.place(origin, &mut |world_pos, block| {
+                    if Chunk::world_coord_chunk(world_pos.as_vec3()) != owner {
+                        crossing += 1;
+                    }
+                    expected.insert(world_pos, block);
+                });
+            }
+        }
+        assert!(crossing > 0, "no structure crosses a chunk border");
+
+        for chunk in &chunks {
+            for (pos, block) in chunk.iter_blocks() {
+                let world_pos = chunk.origin() + pos;
+                let expected = expected.get(&world_pos).copied().unwrap_or_default();
+                assert_eq!(*block, expected, "block at {}", world_pos);
+            }
+        }
+    }
+}
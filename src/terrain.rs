@@ -0,0 +1,567 @@
+use bevy::{math::DVec2, prelude::*};
+use noise::{NoiseFn, OpenSimplex, Perlin, Simplex, SuperSimplex, Value};
+
+use crate::{
+    rng::{seed_for, Feature},
+    structures::BOULDERS,
+    BlockType, CHUNK_SIZE,
+};
+
+/// Shape of the generated terrain. Generation is deterministic for a given config and seed.
+#[derive(Resource, Clone, Debug)]
+pub struct TerrainConfig {
+    /// World y of the surface, shifting all terrain up or down, e.g. to line it up with sea level.
+    pub base_height: i32,
+    /// How many blocks hills rise above, and valleys sink below, `base_height`. Zero, the
+    /// default, generates a flat world.
+    pub hill_height: i32,
+    /// Distance, in blocks, between the highest and lowest points of neighbouring hills and
+    /// valleys. Larger scales give broader, smoother terrain.
+    pub horizontal_scale: f32,
+    /// Separate horizontal scales along x and z, used instead of `horizontal_scale` to stretch
+    /// the terrain along one axis.
+    pub horizontal_scale_xz: Option<Vec2>,
+    /// Noise the hills are shaped by.
+    pub noise_type: NoiseType,
+    /// How far, in blocks, a second noise shifts where the height is sampled, bending the hills
+    /// into more natural, swirly shapes. Zero turns the warp off.
+    pub warp_strength: f32,
+    /// How often, in cycles per block, the warp changes direction. Lower frequencies bend the
+    /// terrain more gradually.
+    pub warp_frequency: f32,
+    /// Lowest world y the surface may be generated at.
+    pub min_height: i32,
+    /// Highest world y the surface may be generated at, keeping terrain within the loaded chunks.
+    pub max_height: i32,
+    /// How many blocks before `min_height` and `max_height` the surface starts to level off, so
+    /// terrain rounds into a plateau instead of being cut flat with a sharp edge. Zero clamps
+    /// without smoothing.
+    pub clamp_smoothing: i32,
+    /// World y of a layer of bedrock, so the world has a solid bottom however the terrain above
+    /// it is shaped.
+    pub floor_height: Option<i32>,
+    /// Largest height difference, in blocks, to a neighbouring column that is still covered in
+    /// grass. Steeper columns show bare stone.
+    pub max_grass_slope: i32,
+    /// Scatter boulders over the surface. Off by default, as it changes the shape of every
+    /// existing world.
+    pub boulders: bool,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            base_height: 5,
+            hill_height: 0,
+            horizontal_scale: 24.,
+            horizontal_scale_xz: None,
+            noise_type: NoiseType::default(),
+            warp_strength: 0.,
+            warp_frequency: 0.02,
+            min_height: 0,
+            max_height: CHUNK_SIZE.y - 1,
+            clamp_smoothing: 0,
+            floor_height: Some(0),
+            max_grass_slope: 1,
+            boulders: false,
+        }
+    }
+}
+
+/// Noise generators to shape hills with, each giving the terrain a different character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoiseType {
+    /// Random heights on a grid, eased in between, so hills line up with the grid.
+    #[default]
+    Value,
+    Perlin,
+    Simplex,
+    OpenSimplex,
+    SuperSimplex,
+}
+
+impl NoiseType {
+    /// The generator, seeded with `seed`.
+    fn generator(self, seed: u64) -> Box<dyn NoiseFn<f64, 2>> {
+        // Generators take 32 bit seeds, so fold the high half in rather than dropping it
+        let seed = (seed ^ (seed >> 32)) as u32;
+        match self {
+            NoiseType::Value => Box::new(Value::new(seed)),
+            NoiseType::Perlin => Box::new(Perlin::new(seed)),
+            NoiseType::Simplex => Box::new(Simplex::new(seed)),
+            NoiseType::OpenSimplex => Box::new(OpenSimplex::new(seed)),
+            NoiseType::SuperSimplex => Box::new(SuperSimplex::new(seed)),
+        }
+    }
+}
+
+/// The surface of one world, with its noise set up once for sampling many columns.
+pub struct Surface<'a> {
+    config: &'a TerrainConfig,
+    height_noise: Box<dyn NoiseFn<f64, 2>>,
+    /// Only set up when the config warps the terrain.
+    warp_noise: Option<Box<dyn NoiseFn<f64, 2>>>,
+}
+
+impl Surface<'_> {
+    /// World y of the topmost solid block of the column at `x`, `z`.
+    pub fn height(&self, x: i32, z: i32) -> i32 {
+        let config = self.config;
+        let hills = if config.hill_height == 0 {
+            0
+        } else {
+            let pos = self.warp(Vec2::new(x as f32, z as f32)) / config.horizontal_scale();
+            let noise = self.height_noise.get(pos.as_dvec2().to_array()) as f32;
+            (noise.clamp(-1., 1.) * config.hill_height as f32).round() as i32
+        };
+
+        config.clamp_height(config.base_height + hills)
+    }
+
+    /// `pos` shifted by up to `warp_strength` along x and z.
+    fn warp(&self, pos: Vec2) -> Vec2 {
+        let Some(warp_noise) = &self.warp_noise else {
+            return pos;
+        };
+
+        let sample = (pos * self.config.warp_frequency).as_dvec2();
+        // Far apart samples of the same noise, so the two offsets don't move together
+        let offset = Vec2::new(
+            warp_noise.get(sample.to_array()) as f32,
+            warp_noise.get((sample + DVec2::new(31.7, 47.3)).to_array()) as f32,
+        );
+        pos + offset.clamp(Vec2::NEG_ONE, Vec2::ONE) * self.config.warp_strength
+    }
+
+    /// Block on top of the column at `x`, `z`, picked by how steep the terrain is around it.
+    pub fn block(&self, x: i32, z: i32) -> BlockType {
+        let height = self.height(x, z);
+        let slope = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .into_iter()
+            .map(|offset| (self.height(x + offset.x, z + offset.y) - height).abs())
+            .max()
+            .unwrap_or(0);
+
+        self.config.surface_block_for_slope(slope)
+    }
+}
+
+impl TerrainConfig {
+    /// The surface of the world with `world_seed`, to sample many columns of.
+    pub fn surface(&self, world_seed: u64) -> Surface<'_> {
+        Surface {
+            config: self,
+            height_noise: self
+                .noise_type
+                .generator(seed_for(world_seed, Feature::Height)),
+            warp_noise: (self.warp_strength != 0.).then(|| {
+                self.noise_type
+                    .generator(seed_for(world_seed, Feature::Warp))
+            }),
+        }
+    }
+
+    /// World y of the topmost solid block of the column at `x`, `z`.
+    pub fn surface_height(&self, world_seed: u64, x: i32, z: i32) -> i32 {
+        self.surface(world_seed).height(x, z)
+    }
+
+    /// Horizontal scale along x and z, at least a block so neighbouring columns never jump
+    /// between unrelated heights.
+    fn horizontal_scale(&self) -> Vec2 {
+        self.horizontal_scale_xz
+            .unwrap_or(Vec2::splat(self.horizontal_scale))
+            .max(Vec2::ONE)
+    }
+
+    /// Keeps `height` between `min_height` and `max_height`, easing it into them over
+    /// `clamp_smoothing` blocks.
+    fn clamp_height(&self, height: i32) -> i32 {
+        let smoothing = self.clamp_smoothing.max(0) as f32;
+        let eased = if smoothing == 0. {
+            height as f32
+        } else {
+            // Approaches the limit without reaching it, however far past it `excess` goes
+            let ease = |excess: f32| smoothing * (1. - (-excess / smoothing).exp());
+            let top = self.max_height as f32 - smoothing;
+            let bottom = self.min_height as f32 + smoothing;
+            let height = height as f32;
+            if height > top {
+                top + ease(height - top)
+            } else if height < bottom {
+                bottom - ease(bottom - height)
+            } else {
+                height
+            }
+        };
+
+        (eased.round() as i32)
+            .max(self.min_height)
+            .min(self.max_height)
+    }
+
+    /// Writes every solid block within the `size` blocks from `region_origin`, in world
+    /// coordinates. Every block only depends on its world position, never on the region it is
+    /// generated in, so the world comes out the same whatever `CHUNK_SIZE` is. New layers must
+    /// keep it that way, like [`StructurePlacer`](crate::structures::StructurePlacer) does by
+    /// picking origins on its own grid.
+    pub fn generate_region(
+        &self,
+        world_seed: u64,
+        region_origin: IVec3,
+        size: IVec3,
+        write: &mut dyn FnMut(IVec3, BlockType),
+    ) {
+        let surface = self.surface(world_seed);
+        for x in region_origin.x..region_origin.x + size.x {
+            for z in region_origin.z..region_origin.z + size.z {
+                let surface_height = surface.height(x, z);
+                for y in region_origin.y..region_origin.y + size.y {
+                    let world_pos = IVec3::new(x, y, z);
+                    if self.floor_height == Some(y) {
+                        write(world_pos, BlockType::Bedrock);
+                    } else if y == surface_height {
+                        write(world_pos, surface.block(x, z));
+                    } else if y < surface_height {
+                        write(world_pos, BlockType::Placeholder);
+                    }
+                }
+            }
+        }
+
+        if self.boulders {
+            BOULDERS.place_in_region(world_seed, region_origin, size, self, write);
+        }
+    }
+
+    /// Block on top of a column whose height differs by up to `slope` from its neighbours.
+    fn surface_block_for_slope(&self, slope: i32) -> BlockType {
+        if slope > self.max_grass_slope {
+            BlockType::Stone
+        } else {
+            BlockType::Grass
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::Chunk;
+
+    const COLUMNS: [(i32, i32); 4] = [(0, 0), (5, -3), (-40, 17), (1000, 1000)];
+    const SEED: u64 = 42;
+
+    /// Surface heights on a grid of columns around the origin.
+    fn sample_heights(config: &TerrainConfig, seed: u64) -> Vec<i32> {
+        let surface = config.surface(seed);
+        (-64..64)
+            .step_by(4)
+            .flat_map(|x| (-64..64).step_by(4).map(move |z| (x, z)))
+            .map(|(x, z)| surface.height(x, z))
+            .collect()
+    }
+
+    /// Every solid block generated in the `size` blocks from `min`, generated as a grid of
+    /// `tile`-sized regions the way chunks are.
+    fn generate_tiled(
+        config: &TerrainConfig,
+        min: IVec3,
+        size: IVec3,
+        tile: IVec3,
+    ) -> HashMap<IVec3, BlockType> {
+        let tiles = size / tile;
+        let mut blocks = HashMap::new();
+        for x in 0..tiles.x {
+            for y in 0..tiles.y {
+                for z in 0..tiles.z {
+                    let region_origin = min + IVec3::new(x, y, z) * tile;
+                    config.generate_region(SEED, region_origin, tile, &mut |world_pos, block| {
+                        blocks.insert(world_pos, block);
+                    });
+                }
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn generated_world_does_not_depend_on_the_chunk_size() {
+        let config = TerrainConfig {
+            hill_height: 12,
+            boulders: true,
+            ..default()
+        };
+        let (min, size) = (IVec3::new(-32, -16, -32), IVec3::splat(64));
+        let large = generate_tiled(&config, min, size, IVec3::splat(32));
+        let small = generate_tiled(&config, min, size, IVec3::splat(16));
+
+        assert!(large.values().any(|block| *block == BlockType::Stone));
+        assert_eq!(large, small);
+        let fixed = IVec3::new(3, config.base_height, -7);
+        assert_eq!(large.get(&fixed), small.get(&fixed));
+    }
+
+    #[test]
+    fn base_height_shifts_the_surface_everywhere() {
+        let config = TerrainConfig::default();
+        let raised = TerrainConfig {
+            base_height: config.base_height + 3,
+            ..config.clone()
+        };
+
+        for (x, z) in COLUMNS {
+            assert_eq!(
+                raised.surface_height(SEED, x, z),
+                config.surface_height(SEED, x, z) + 3
+            );
+        }
+    }
+
+    #[test]
+    fn hills_depend_on_the_seed_and_stay_within_their_height() {
+        let config = TerrainConfig {
+            hill_height: 4,
+            ..default()
+        };
+        let heights = |seed| sample_heights(&config, seed);
+
+        let hills = heights(SEED);
+        assert_eq!(hills, heights(SEED));
+        assert_ne!(hills, heights(SEED + 1));
+        let lowest = *hills.iter().min().unwrap();
+        let highest = *hills.iter().max().unwrap();
+        assert!(lowest < highest, "the surface is flat");
+        assert!(lowest >= config.base_height - config.hill_height);
+        assert!(highest <= config.base_height + config.hill_height);
+    }
+
+    /// Summed height differences between neighbouring columns of the chunk at the origin, along
+    /// x and along z.
+    fn height_variation(config: &TerrainConfig) -> (i32, i32) {
+        let height = |x, z| config.surface_height(SEED, x, z);
+        let mut variation = (0, 0);
+        for x in 0..CHUNK_SIZE.x - 1 {
+            for z in 0..CHUNK_SIZE.z - 1 {
+                variation.0 += (height(x + 1, z) - height(x, z)).abs();
+                variation.1 += (height(x, z + 1) - height(x, z)).abs();
+            }
+        }
+        variation
+    }
+
+    #[test]
+    fn larger_horizontal_scales_vary_more_smoothly() {
+        let config = TerrainConfig {
+            base_height: 16,
+            hill_height: 8,
+            ..default()
+        };
+        let with_scale = |horizontal_scale| TerrainConfig {
+            horizontal_scale,
+            ..config.clone()
+        };
+
+        let (tight_x, tight_z) = height_variation(&with_scale(4.));
+        let (broad_x, broad_z) = height_variation(&with_scale(64.));
+        assert!(broad_x + broad_z < tight_x + tight_z);
+        assert_eq!(height_variation(&with_scale(4.)), (tight_x, tight_z));
+
+        // Stretched along x, the terrain is smoother along x than along z
+        let (stretched_x, stretched_z) = height_variation(&TerrainConfig {
+            horizontal_scale_xz: Some(Vec2::new(64., 4.)),
+            ..config
+        });
+        assert!(stretched_x < stretched_z);
+    }
+
+    #[test]
+    fn each_noise_type_is_deterministic_and_distinct() {
+        let noise_types = [
+            NoiseType::Value,
+            NoiseType::Perlin,
+            NoiseType::Simplex,
+            NoiseType::OpenSimplex,
+            NoiseType::SuperSimplex,
+        ];
+        let heights = |noise_type, seed| {
+            let config = TerrainConfig {
+                base_height: 16,
+                hill_height: 12,
+                noise_type,
+                ..default()
+            };
+            sample_heights(&config, seed)
+        };
+
+        let all: Vec<_> = noise_types
+            .iter()
+            .map(|&noise_type| heights(noise_type, SEED))
+            .collect();
+        for (i, &noise_type) in noise_types.iter().enumerate() {
+            assert_eq!(heights(noise_type, SEED), all[i]);
+            assert_ne!(heights(noise_type, SEED + 1), all[i]);
+            assert!(
+                !all[..i].contains(&all[i]),
+                "{:?} shapes the same hills as another noise type",
+                noise_type
+            );
+        }
+    }
+
+    #[test]
+    fn domain_warp_changes_the_heights_deterministically() {
+        let config = TerrainConfig {
+            base_height: 16,
+            hill_height: 12,
+            ..default()
+        };
+        let warped = TerrainConfig {
+            warp_strength: 16.,
+            ..config.clone()
+        };
+        let heights = |config: &TerrainConfig| sample_heights(config, SEED);
+
+        assert_eq!(heights(&warped), heights(&warped));
+        assert_ne!(heights(&warped), heights(&config));
+        // Without strength the frequency has no effect
+        let unwarped = TerrainConfig {
+            warp_frequency: 0.5,
+            ..config.clone()
+        };
+        assert_eq!(heights(&unwarped), heights(&config));
+    }
+
+    #[test]
+    fn generated_columns_stay_below_a_tight_max_height() {
+        let config = TerrainConfig {
+            base_height: 20,
+            max_height: 3,
+            ..default()
+        };
+        let chunk = Chunk::default().generate_terrain(&config);
+
+        for x in 0..CHUNK_SIZE.x {
+            for z in 0..CHUNK_SIZE.z {
+                let top = chunk.column_top(x, z).unwrap();
+                assert!(
+                    top <= config.max_height,
+                    "column {}, {} reaches {}",
+                    x,
+                    z,
+                    top
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn floor_layer_is_solid_bedrock_even_below_the_surface() {
+        // The surface dips below the floor here, leaving it with only air above
+        let config = TerrainConfig {
+            base_height: -30,
+            min_height: -64,
+            floor_height: Some(-20),
+            ..default()
+        };
+
+        for chunk_coords in [IVec3::new(0, -1, 0), IVec3::new(3, -1, -2)] {
+            let chunk = Chunk {
+                chunk_coords,
+                world_seed: SEED,
+                ..default()
+            }
+            .generate_terrain(&config);
+            let floor = -20 - chunk.origin().y;
+            for x in 0..CHUNK_SIZE.x {
+                for z in 0..CHUNK_SIZE.z {
+                    assert_eq!(
+                        chunk.get_block(IVec3::new(x, floor, z)),
+                        Some(&BlockType::Bedrock)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smoothed_heights_level_off_below_the_max_height() {
+        let config = TerrainConfig {
+            hill_height: 0,
+            max_height: 20,
+            clamp_smoothing: 4,
+            ..default()
+        };
+        let heights: Vec<_> = (10..=60)
+            .map(|base_height| {
+                TerrainConfig {
+                    base_height,
+                    ..config.clone()
+                }
+                .surface_height(SEED, 0, 0)
+            })
+            .collect();
+
+        assert!(heights.iter().all(|&height| height <= config.max_height));
+        assert!(heights.windows(2).all(|pair| pair[0] <= pair[1]));
+        // Far above the max the surface ends up at it, just below it already levels off
+        assert_eq!(heights.last(), Some(&config.max_height));
+        assert!(heights[19 - 10] < 19);
+        // Out of reach of the smoothing, heights are unchanged
+        assert_eq!(heights[0], 10);
+    }
+
+    #[test]
+    fn steep_columns_are_topped_with_stone() {
+        let config = TerrainConfig::default();
+
+        assert_eq!(
+            config.surface_block_for_slope(config.max_grass_slope + 1),
+            BlockType::Stone
+        );
+        assert_eq!(
+            config.surface_block_for_slope(config.max_grass_slope),
+            BlockType::Grass
+        );
+    }
+
+    #[test]
+    fn flat_columns_are_topped_with_grass() {
+        let config = TerrainConfig {
+            hill_height: 0,
+            ..default()
+        };
+        for (x, z) in COLUMNS {
+            assert_eq!(config.surface(SEED).block(x, z), BlockType::Grass);
+        }
+    }
+
+    #[test]
+    fn generated_slopes_show_stone_and_gentle_ground_grass() {
+        let config = TerrainConfig {
+            base_height: 16,
+            hill_height: 12,
+            horizontal_scale: 8.,
+            ..default()
+        };
+        let chunk = Chunk {
+            world_seed: SEED,
+            ..default()
+        }
+        .generate_terrain(&config);
+
+        let mut tops = Vec::new();
+        for x in 0..CHUNK_SIZE.x {
+            for z in 0..CHUNK_SIZE.z {
+                let y = chunk.column_top(x, z).unwrap();
+                let top = *chunk.get_block(IVec3::new(x, y, z)).unwrap();
+                assert_eq!(top, config.surface(SEED).block(x, z), "column {}, {}", x, z);
+                tops.push(top);
+            }
+        }
+        assert!(tops.contains(&BlockType::Stone));
+        assert!(tops.contains(&BlockType::Grass));
+    }
+}
@@ -0,0 +1,189 @@
+//! Building blocks for tests that run the game's systems without a window or GPU.
+
+use bevy::{
+    asset::AssetPlugin,
+    ecs::event::Event,
+    input::{
+        keyboard::KeyboardInput,
+        mouse::{MouseButtonInput, MouseMotion, MouseScrollUnit, MouseWheel},
+        ButtonState, InputPlugin,
+    },
+    prelude::*,
+};
+
+use crate::{
+    AppState, BlockAtlasHandle, BlockType, BlockUvs, Chunk, GraphicsSettings,
+    RequiresMeshGeneration, UvMappings, WorldPlugin,
+};
+
+/// Every block textured with the whole atlas, so meshing never misses a texture.
+pub fn test_uv_mappings() -> UvMappings {
+    let uvs = [[0., 0.], [1., 0.], [1., 1.], [0., 1.]];
+    let block_uvs = BlockUvs {
+        top: uvs,
+        side: uvs,
+        bottom: uvs,
+        side_overlay: None,
+    };
+    UvMappings(
+        BlockType::ALL
+            .into_iter()
+            .map(|block_type| (block_type, block_uvs))
+            .collect(),
+    )
+}
+
+/// The world's systems on top of [`MinimalPlugins`], starting straight in [`AppState::Game`] with
+/// a stand-in atlas, so nothing is rendered and no textures are loaded. Input is fed in with
+/// helpers like [`press_key`] and takes effect on the next update.
+pub fn test_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(InputPlugin)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<Image>()
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .add_asset::<TextureAtlas>()
+        .init_resource::<GraphicsSettings>()
+        .add_plugin(WorldPlugin)
+        .insert_resource(test_uv_mappings())
+        .add_state(AppState::Game);
+
+    let atlas = TextureAtlas::new_empty(Handle::default(), Vec2::ONE);
+    let atlas = app.world.resource_mut::<Assets<TextureAtlas>>().add(atlas);
+    app.insert_resource(BlockAtlasHandle(atlas));
+
+    app
+}
+
+/// Updates `app` until every chunk in `region` is loaded and has its mesh, with no remesh pending,
+/// returning how many updates that took, or an error naming the chunks still missing after
+/// `max_updates`.
+pub fn run_until_meshed(
+    app: &mut App,
+    region: &[IVec3],
+    max_updates: usize,
+) -> Result<usize, String> {
+    let mut missing = region.to_vec();
+    for updates in 1..=max_updates {
+        app.update();
+
+        let mut chunks = app
+            .world
+            .query_filtered::<(&Chunk, &Handle<Mesh>), Without<RequiresMeshGeneration>>();
+        let meshes = app.world.resource::<Assets<Mesh>>();
+        let meshed: Vec<_> = chunks
+            .iter(&app.world)
+            .filter(|(_, mesh)| meshes.contains(*mesh))
+            .map(|(chunk, _)| chunk.chunk_coords)
+            .collect();
+
+        missing = region
+            .iter()
+            .copied()
+            .filter(|coords| !meshed.contains(coords))
+            .collect();
+        if missing.is_empty() {
+            return Ok(updates);
+        }
+    }
+
+    Err(format!(
+        "chunks {:?} still not meshed after {} updates",
+        missing, max_updates
+    ))
+}
+
+/// Updates `app` `updates` times.
+pub fn tick(app: &mut App, updates: usize) {
+    for _ in 0..updates {
+        app.update();
+    }
+}
+
+fn send<E: Event>(app: &mut App, event: E) {
+    app.world.resource_mut::<Events<E>>().send(event);
+}
+
+/// Sends the events a window would for `key`, so it is pressed and just pressed on the next
+/// update, and stays pressed until [`release_key`].
+pub fn press_key(app: &mut App, key: KeyCode) {
+    send_key(app, key, ButtonState::Pressed);
+}
+
+pub fn release_key(app: &mut App, key: KeyCode) {
+    send_key(app, key, ButtonState::Released);
+}
+
+fn send_key(app: &mut App, key: KeyCode, state: ButtonState) {
+    send(
+        app,
+        KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key),
+            state,
+        },
+    );
+}
+
+pub fn press_mouse_button(app: &mut App, button: MouseButton) {
+    send(
+        app,
+        MouseButtonInput {
+            button,
+            state: ButtonState::Pressed,
+        },
+    );
+}
+
+pub fn release_mouse_button(app: &mut App, button: MouseButton) {
+    send(
+        app,
+        MouseButtonInput {
+            button,
+            state: ButtonState::Released,
+        },
+    );
+}
+
+pub fn move_mouse(app: &mut App, delta: Vec2) {
+    send(app, MouseMotion { delta });
+}
+
+/// Scrolls the mouse wheel by `lines`, positive scrolling up.
+pub fn scroll_mouse(app: &mut App, lines: f32) {
+    send(
+        app,
+        MouseWheel {
+            unit: MouseScrollUnit::Line,
+            x: 0.,
+            y: lines,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Scrolled(f32);
+
+    fn scroll_system(mut wheel_events: EventReader<MouseWheel>, mut scrolled: ResMut<Scrolled>) {
+        for event in wheel_events.iter() {
+            scrolled.0 += event.y;
+        }
+    }
+
+    #[test]
+    fn scrolling_reaches_the_systems_on_the_next_update() {
+        let mut app = test_app();
+        app.init_resource::<Scrolled>().add_system(scroll_system);
+
+        scroll_mouse(&mut app, 2.);
+        scroll_mouse(&mut app, -0.5);
+        tick(&mut app, 3);
+        assert_eq!(app.world.resource::<Scrolled>().0, 1.5);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{Chunk, CHUNK_SIZE};
+
+/// Finds the air voxel closest to `from`, in world coordinates, with at least `clearance` air
+/// voxels stacked from it upwards, e.g. 2 for somewhere a standing entity fits. Only looks up to
+/// `max_radius` blocks away along each axis. Voxels in chunks that aren't among `chunks` count as
+/// solid, so nothing is ever placed in unloaded terrain.
+pub fn nearest_air_voxel<'a>(
+    from: IVec3,
+    max_radius: i32,
+    clearance: i32,
+    chunks: impl IntoIterator<Item = &'a Chunk>,
+) -> Option<IVec3> {
+    let chunks: HashMap<IVec3, &Chunk> = chunks
+        .into_iter()
+        .map(|chunk| (chunk.chunk_coords, chunk))
+        .collect();
+
+    let is_air = |pos: IVec3| {
+        let chunk_coords = Chunk::world_coord_chunk(pos.as_vec3());
+        chunks.get(&chunk_coords).is_some_and(|chunk| {
+            chunk
+                .get_block(pos - chunk.origin())
+                .is_some_and(|block| block.is_transparent())
+        })
+    };
+    let fits = |pos: IVec3| (0..clearance.max(1)).all(|dy| is_air(pos + IVec3::Y * dy));
+
+    let mut nearest: Option<(i32, IVec3)> = None;
+    for radius in 0..=max_radius {
+        // Every voxel in this shell and beyond is at least `radius` away
+        if nearest.is_some_and(|(distance_squared, _)| distance_squared < radius * radius) {
+            break;
+        }
+
+        for offset in shell(radius) {
+            let distance_squared = offset.dot(offset);
+            if nearest.is_some_and(|(nearest_squared, _)| nearest_squared <= distance_squared) {
+                continue;
+            }
+
+            if fits(from + offset) {
+                nearest = Some((distance_squared, from + offset));
+            }
+        }
+    }
+
+    nearest.map(|(_, pos)| pos)
+}
+
+/// Whether nothing solid is above the voxel at `world_pos` in any of `chunks`, e.g. for deciding
+/// where mobs may spawn. Chunks that aren't loaded count as open.
+pub fn has_sky_access<'a>(world_pos: IVec3, chunks: impl IntoIterator<Item = &'a Chunk>) -> bool {
+    let column = Chunk::world_coord_chunk(world_pos.as_vec3());
+    chunks
+        .into_iter()
+        .filter(|chunk| chunk.chunk_coords.x == column.x && chunk.chunk_coords.z == column.z)
+        .all(|chunk| {
+            let local = world_pos - chunk.origin();
+            ((local.y + 1).max(0)..CHUNK_SIZE.y).all(|y| {
+                !matches!(
+                    chunk.get_block(IVec3::new(local.x, y, local.z)),
+                    Some(block) if !block.is_transparent()
+                )
+            })
+        })
+}
+
+/// Offsets on the surface of the cube reaching `radius` voxels out from the center.
+fn shell(radius: i32) -> impl Iterator<Item = IVec3> {
+    (-radius..=radius).flat_map(move |x| {
+        (-radius..=radius).flat_map(move |y| {
+            (-radius..=radius)
+                .map(move |z| IVec3::new(x, y, z))
+                .filter(move |offset| offset.abs().max_element() == radius)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockType;
+
+    fn chunk_at(chunk_coords: IVec3) -> Chunk {
+        Chunk {
+            chunk_coords,
+            ..default()
+        }
+    }
+
+    fn solid_chunk_at(chunk_coords: IVec3) -> Chunk {
+        let mut chunk = chunk_at(chunk_coords);
+        for (_, block) in chunk.iter_blocks_mut() {
+            *block = BlockType::Stone;
+        }
+        chunk
+    }
+
+    fn set_block(chunk: &mut Chunk, world_pos: IVec3, block: BlockType) {
+        let origin = chunk.origin();
+        *chunk.get_block_mut(world_pos - origin).unwrap() = block;
+    }
+
+    #[test]
+    fn open_column_has_sky_access() {
+        let mut chunk = chunk_at(IVec3::ZERO);
+        set_block(&mut chunk, IVec3::new(3, 4, 3), BlockType::Stone);
+
+        assert!(has_sky_access(IVec3::new(3, 4, 3), [&chunk]));
+        assert!(has_sky_access(IVec3::new(5, 0, 5), [&chunk]));
+    }
+
+    #[test]
+    fn roof_in_the_chunk_above_blocks_sky_access() {
+        let below = chunk_at(IVec3::ZERO);
+        let mut above = chunk_at(IVec3::Y);
+        let roof = IVec3::new(3, CHUNK_SIZE.y + 2, 3);
+        set_block(&mut above, roof, BlockType::Stone);
+
+        assert!(!has_sky_access(IVec3::new(3, 1, 3), [&below, &above]));
+        assert!(has_sky_access(roof, [&below, &above]));
+        assert!(has_sky_access(IVec3::new(4, 1, 3), [&below, &above]));
+    }
+
+    #[test]
+    fn air_voxel_is_its_own_nearest() {
+        let chunk = chunk_at(IVec3::ZERO);
+        let pos = IVec3::new(4, 10, 4);
+
+        assert_eq!(nearest_air_voxel(pos, 4, 2, [&chunk]), Some(pos));
+    }
+
+    #[test]
+    fn buried_voxel_finds_the_closest_pocket_with_clearance() {
+        let mut chunk = solid_chunk_at(IVec3::ZERO);
+        let from = IVec3::new(8, 10, 8);
+        // One high, right next to it
+        set_block(&mut chunk, from + IVec3::X, BlockType::Air);
+        // Two high, a bit further away
+        let pocket = from - IVec3::X * 2;
+        set_block(&mut chunk, pocket, BlockType::Air);
+        set_block(&mut chunk, pocket + IVec3::Y, BlockType::Air);
+
+        assert_eq!(
+            nearest_air_voxel(from, 4, 1, [&chunk]),
+            Some(from + IVec3::X)
+        );
+        assert_eq!(nearest_air_voxel(from, 4, 2, [&chunk]), Some(pocket));
+        assert_eq!(nearest_air_voxel(from, 1, 2, [&chunk]), None);
+    }
+
+    #[test]
+    fn unloaded_chunks_are_never_picked() {
+        let chunk = solid_chunk_at(IVec3::ZERO);
+        let from = IVec3::new(CHUNK_SIZE.x - 1, 10, 8);
+
+        assert_eq!(nearest_air_voxel(from, 4, 1, [&chunk]), None);
+
+        let open_neighbour = chunk_at(IVec3::X);
+        assert_eq!(
+            nearest_air_voxel(from, 4, 1, [&chunk, &open_neighbour]),
+            Some(from + IVec3::X)
+        );
+    }
+}